@@ -0,0 +1,288 @@
+use std::f64::consts::PI;
+
+use crate::{
+    analysis::{AnalysisError, DcOperatingPoint, Probe, find_column, record},
+    circuit::Circuit,
+    component::Mode,
+    numerical::c64,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sweep {
+    /// `points` frequencies evenly spaced between start and stop.
+    Linear { points: usize },
+    /// `points` frequencies per factor of ten.
+    Decade { points: usize },
+    /// `points` frequencies per factor of two.
+    Octave { points: usize },
+}
+
+impl Sweep {
+    /// Whether it can run from `start_hz` to `stop_hz`: forwards, from a positive
+    /// frequency, as DC has no place on the logarithmic axis of a Bode plot.
+    pub fn spans(&self, start_hz: f64, stop_hz: f64) -> bool {
+        0. < start_hz && start_hz <= stop_hz && stop_hz.is_finite()
+    }
+
+    pub fn frequencies(&self, start_hz: f64, stop_hz: f64) -> Vec<f64> {
+        let logarithmic = |base: f64, points: usize| {
+            let step = base.powf(1. / points.max(1) as f64);
+            let count = ((stop_hz / start_hz).ln() / step.ln() + 1e-9).floor() as usize;
+            (0..=count)
+                .map(|i| start_hz * step.powi(i as i32))
+                .collect()
+        };
+
+        match *self {
+            Sweep::Linear { points: 0 } => vec![],
+            Sweep::Linear { points: 1 } => vec![start_hz],
+            Sweep::Linear { points } => (0..points)
+                .map(|i| start_hz + (stop_hz - start_hz) * i as f64 / (points - 1) as f64)
+                .collect(),
+            Sweep::Decade { points } => logarithmic(10., points),
+            Sweep::Octave { points } => logarithmic(2., points),
+        }
+    }
+}
+
+/// Small-signal frequency sweep. Reactive components are stamped as their
+/// admittances, AC sources as phasors and DC sources are zeroed.
+///
+/// Nonlinear components are linearized around the operating point, which it runs a
+/// [`DcOperatingPoint`] for first. A failure to find it is reported at 0Hz.
+#[derive(Debug, Clone)]
+pub struct AcSweep {
+    pub sweep: Sweep,
+    pub start_hz: f64,
+    pub stop_hz: f64,
+    pub probes: Vec<Probe>,
+}
+
+impl AcSweep {
+    pub fn run(&self, circuit: &mut Circuit) -> Result<FrequencyResponse, AnalysisError> {
+        if !self.sweep.spans(self.start_hz, self.stop_hz) {
+            return Err(AnalysisError::InvalidSweep {
+                start_hz: self.start_hz,
+                stop_hz: self.stop_hz,
+            });
+        }
+
+        if circuit.is_nonlinear(Mode::DC_FULL) {
+            DcOperatingPoint.run(circuit)?;
+        }
+
+        let frequency = self.sweep.frequencies(self.start_hz, self.stop_hz);
+
+        let mut response = FrequencyResponse {
+            frequency: vec![],
+            columns: self.probes.iter().map(|p| (p.clone(), vec![])).collect(),
+        };
+
+        for f in frequency {
+            let mode = Mode::Ac { omega: 2. * PI * f };
+
+            circuit
+                .solve(mode)
+                .map_err(|error| AnalysisError::at(f, error))?;
+            circuit.post_stamp_all(mode);
+
+            response.frequency.push(f);
+            record(&mut response.columns, circuit)?;
+        }
+
+        Ok(response)
+    }
+}
+
+/// Complex probe values against frequency, one column per probe.
+#[derive(Debug, Clone)]
+pub struct FrequencyResponse {
+    pub frequency: Vec<f64>,
+    pub columns: Vec<(Probe, Vec<c64>)>,
+}
+
+impl FrequencyResponse {
+    /// Looks up a column by the probe's printed name, e.g. `V(out)`.
+    pub fn column(&self, probe: &str) -> Option<&[c64]> {
+        find_column(&self.columns, probe)
+    }
+
+    /// Magnitude in dB against the decade, `log10(f)`, as a Bode plot for [`crate::print_chart`].
+    pub fn magnitude_db(&self, probe: &str) -> Option<Vec<(f64, f64)>> {
+        let values = self.column(probe)?;
+
+        Some(
+            self.frequency
+                .iter()
+                .zip(values)
+                .map(|(&f, z)| (f.log10(), 20. * z.norm().log10()))
+                .collect(),
+        )
+    }
+
+    /// Phase in degrees against the decade, `log10(f)`.
+    pub fn phase_deg(&self, probe: &str) -> Option<Vec<(f64, f64)>> {
+        let values = self.column(probe)?;
+
+        Some(
+            self.frequency
+                .iter()
+                .zip(values)
+                .map(|(&f, z)| (f.log10(), z.arg().to_degrees()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::{AC1Source, Capacitor, DC2Source, Ground, Inductor, Resistor};
+
+    use super::*;
+
+    #[test]
+    fn test_sweep_frequencies() {
+        let decade = Sweep::Decade { points: 10 }.frequencies(1., 1e3);
+        assert_eq!(decade.len(), 31);
+        assert!((decade[10] - 10.).abs() < 1e-9);
+        assert!((decade[30] - 1e3).abs() < 1e-6);
+
+        let octave = Sweep::Octave { points: 1 }.frequencies(100., 1600.);
+        assert_eq!(octave, vec![100., 200., 400., 800., 1600.]);
+
+        let linear = Sweep::Linear { points: 5 }.frequencies(100., 500.);
+        assert_eq!(linear, vec![100., 200., 300., 400., 500.]);
+    }
+
+    #[test]
+    fn test_invalid_sweep() {
+        let mut circuit = Circuit::new();
+
+        for (sweep, start_hz, stop_hz) in [
+            (Sweep::Decade { points: 10 }, 0., 1e3),
+            (Sweep::Octave { points: 10 }, -1., 1e3),
+            (Sweep::Decade { points: 10 }, 1e3, 1.),
+            (Sweep::Linear { points: 10 }, 100., 0.),
+            (Sweep::Linear { points: 10 }, -100., 100.),
+            (Sweep::Linear { points: 10 }, 0., 100.),
+            (Sweep::Decade { points: 10 }, 1., f64::INFINITY),
+            (Sweep::Decade { points: 10 }, f64::NAN, 1e3),
+        ] {
+            let sweep = AcSweep {
+                sweep,
+                start_hz,
+                stop_hz,
+                probes: vec![],
+            };
+
+            assert!(matches!(
+                sweep.run(&mut circuit),
+                Err(AnalysisError::InvalidSweep { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_rc_lowpass() {
+        let mut circuit = Circuit::new();
+        let [vin, bias, out, gnd] = ["in", "bias", "out", "gnd"].map(|net| circuit.net(net));
+
+        let source = AC1Source {
+            amplitude_volt: 2.,
+            frequency_hz: 0.,
+            phase_rad: 0.,
+        };
+
+        // the DC bias does not take part in the small-signal response
+        circuit.put(source, None, [vin]);
+        circuit.put(DC2Source { voltage_volt: 5. }, None, [bias, vin]);
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            None,
+            [bias, out],
+        );
+        circuit.put(
+            Capacitor {
+                capacitance_f: 1e-6,
+            },
+            Some("C1".into()),
+            [out, gnd],
+        );
+        circuit.put(Ground, None, [gnd]);
+
+        let corner_hz = 1. / (2. * PI * 1e3 * 1e-6);
+
+        let response = AcSweep {
+            sweep: Sweep::Linear { points: 1 },
+            start_hz: corner_hz,
+            stop_hz: corner_hz,
+            probes: vec![Probe::voltage("out"), Probe::parameter("C1", "I")],
+        }
+        .run(&mut circuit)
+        .unwrap();
+
+        let out = response.column("V(out)").unwrap()[0];
+        assert!((out.norm() - 2. / 2f64.sqrt()).abs() < 1e-9);
+        assert!((out.arg().to_degrees() + 45.).abs() < 1e-9);
+
+        // j omega C V, leading the voltage across it by 90 degrees
+        let i = response.column("I(C1)").unwrap()[0];
+        let expected = c64::imag(2. * PI * corner_hz * 1e-6) * out;
+        assert!((i - expected).norm() < 1e-12, "{:?}", i);
+        assert!((i.norm() - 2e-3 / 2f64.sqrt()).abs() < 1e-9, "{:?}", i);
+
+        let [(_, db)] = response.magnitude_db("V(out)").unwrap()[..] else {
+            panic!()
+        };
+        assert!((db - 20. * 2f64.sqrt().log10()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_lc_resonance() {
+        let mut circuit = Circuit::new();
+        let [vin, mid, out, gnd] = ["in", "mid", "out", "gnd"].map(|net| circuit.net(net));
+
+        let source = AC1Source {
+            amplitude_volt: 1.,
+            frequency_hz: 0.,
+            phase_rad: 0.,
+        };
+
+        circuit.put(source, None, [vin]);
+        circuit.put(
+            Resistor {
+                resistance_ohm: 10.,
+            },
+            None,
+            [vin, mid],
+        );
+        circuit.put(Inductor { inductance_h: 1e-3 }, None, [mid, out]);
+        circuit.put(
+            Capacitor {
+                capacitance_f: 1e-6,
+            },
+            None,
+            [out, gnd],
+        );
+        circuit.put(Ground, None, [gnd]);
+
+        // series resonance, the capacitor sees Q times the input
+        let resonance_hz = 1. / (2. * PI * (1e-3f64 * 1e-6).sqrt());
+        let q = (1e-3f64 / 1e-6).sqrt() / 10.;
+
+        let response = AcSweep {
+            sweep: Sweep::Linear { points: 1 },
+            start_hz: resonance_hz,
+            stop_hz: resonance_hz,
+            probes: vec![Probe::voltage("out")],
+        }
+        .run(&mut circuit)
+        .unwrap();
+
+        let out = response.column("V(out)").unwrap()[0];
+        assert!((out.norm() - q).abs() < 1e-6);
+        assert!((out.arg().to_degrees() + 90.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    circuit::{Circuit, SolveError},
+    numerical::{SingularMatrix, c64},
+};
+
+mod ac;
+mod op;
+mod transient;
+
+pub use ac::*;
+pub use op::*;
+pub use transient::*;
+
+/// A quantity recorded by an analysis, printed SPICE-style as `V(out)` or `I(R1)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Probe {
+    Voltage {
+        net: String,
+    },
+    Parameter {
+        component: String,
+        parameter: String,
+    },
+}
+
+impl Probe {
+    pub fn voltage(net: impl ToString) -> Self {
+        Probe::Voltage {
+            net: net.to_string(),
+        }
+    }
+
+    pub fn parameter(component: impl ToString, parameter: impl ToString) -> Self {
+        Probe::Parameter {
+            component: component.to_string(),
+            parameter: parameter.to_string(),
+        }
+    }
+
+    pub fn read(&self, circuit: &Circuit) -> Option<c64> {
+        match self {
+            Probe::Voltage { net } => circuit.voltage(net),
+            Probe::Parameter {
+                component,
+                parameter,
+            } => circuit.parameter(component, parameter),
+        }
+    }
+}
+
+impl Display for Probe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Probe::Voltage { net } => write!(f, "V({})", net),
+            Probe::Parameter {
+                component,
+                parameter,
+            } => write!(f, "{}({})", parameter, component),
+        }
+    }
+}
+
+/// Reads every probe into its column.
+fn record(columns: &mut [(Probe, Vec<c64>)], circuit: &Circuit) -> Result<(), AnalysisError> {
+    for (probe, values) in columns {
+        let value = probe
+            .read(circuit)
+            .ok_or_else(|| AnalysisError::UnknownProbe {
+                probe: probe.clone(),
+            })?;
+
+        values.push(value);
+    }
+
+    Ok(())
+}
+
+fn find_column<'a>(columns: &'a [(Probe, Vec<c64>)], probe: &str) -> Option<&'a [c64]> {
+    columns
+        .iter()
+        .find(|(p, _)| p.to_string() == probe)
+        .map(|(_, values)| &values[..])
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnalysisError {
+    /// The equations had no unique solution at a time or frequency point.
+    Singular {
+        point: f64,
+        error: SingularMatrix,
+    },
+    UnknownProbe {
+        probe: Probe,
+    },
+    /// The Newton–Raphson loop did not converge at a time or frequency point.
+    NonConvergence {
+        point: f64,
+        iterations: usize,
+    },
+    /// The behavioral source called `name` had no real, finite value at a time or frequency point.
+    Undefined {
+        point: f64,
+        name: Option<String>,
+    },
+    /// The adaptive timestep could not meet the error tolerance even at its minimum.
+    TimestepTooSmall {
+        point: f64,
+        step_s: f64,
+    },
+    /// The sweep runs backwards, or from a frequency that isn't positive.
+    InvalidSweep {
+        start_hz: f64,
+        stop_hz: f64,
+    },
+    /// The transient runs backwards or forever, or its step isn't a positive, finite time.
+    InvalidTimeSpan {
+        start_s: f64,
+        stop_s: f64,
+        step_s: f64,
+    },
+    /// The adaptive timestep's bounds aren't positive and finite, or don't hold `step_s`.
+    InvalidTimestep {
+        min_step_s: f64,
+        step_s: f64,
+        max_step_s: f64,
+    },
+}
+
+impl AnalysisError {
+    fn at(point: f64, error: SolveError) -> Self {
+        match error {
+            SolveError::Singular(error) => AnalysisError::Singular { point, error },
+            SolveError::NonConvergence { iterations } => {
+                AnalysisError::NonConvergence { point, iterations }
+            }
+            SolveError::Undefined { name } => AnalysisError::Undefined { point, name },
+        }
+    }
+}
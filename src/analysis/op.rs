@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use crate::{
+    analysis::AnalysisError,
+    circuit::{Circuit, ComponentParameters, SolveError},
+    component::Mode,
+    numerical::c64,
+    printing::print_table,
+};
+
+/// Which strategy found the operating point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Convergence {
+    /// Plain Newton–Raphson from the present state.
+    Newton,
+    /// A shunt from every node to ground, reduced in decades down to none.
+    GminStepping,
+    /// The independent sources ramped up from zero to full value.
+    SourceStepping,
+}
+
+/// The `.op` analysis: solves for the DC operating point with capacitors open,
+/// inductors shorted and sources at their DC values. Reactive components keep
+/// the solution as their state, so a [`crate::Transient`] run afterwards starts from it.
+///
+/// When Newton–Raphson does not converge, or the equations are singular, it falls
+/// back to Gmin stepping and then to source stepping, each continuing from the
+/// previous solution. A node only capacitors connect to keeps the smallest shunt.
+#[derive(Debug, Clone, Default)]
+pub struct DcOperatingPoint;
+
+impl DcOperatingPoint {
+    const GMIN_STEPS: [f64; 10] = [1e-2, 1e-3, 1e-4, 1e-5, 1e-6, 1e-7, 1e-8, 1e-9, 1e-10, 1e-12];
+    const SOURCE_STEPS: usize = 20;
+
+    pub fn run(&self, circuit: &mut Circuit) -> Result<OperatingPoint, AnalysisError> {
+        let convergence = Self::converge(circuit).map_err(|error| AnalysisError::at(0., error))?;
+        circuit.post_stamp_all(Mode::DC_FULL);
+
+        let mut nets: Vec<_> = circuit.nets().collect();
+        nets.sort_by_key(|&(_, idx)| idx);
+
+        let voltages = nets
+            .into_iter()
+            .map(|(net, idx)| (net.to_owned(), circuit.equations.x[idx as usize]))
+            .collect();
+
+        Ok(OperatingPoint {
+            voltages,
+            components: circuit.components(),
+            convergence,
+        })
+    }
+
+    fn converge(circuit: &mut Circuit) -> Result<Convergence, SolveError> {
+        match circuit.solve(Mode::DC_FULL) {
+            Err(SolveError::NonConvergence { .. } | SolveError::Singular(_)) => {}
+            solved => return solved.map(|_| Convergence::Newton),
+        }
+
+        if Self::gmin_stepping(circuit).is_ok() {
+            return Ok(Convergence::GminStepping);
+        }
+
+        for step in 1..=Self::SOURCE_STEPS {
+            let source_scale = step as f64 / Self::SOURCE_STEPS as f64;
+            circuit.solve(Mode::Dc { source_scale })?;
+        }
+
+        Ok(Convergence::SourceStepping)
+    }
+
+    fn gmin_stepping(circuit: &mut Circuit) -> Result<(), SolveError> {
+        let gmin = circuit.newton.gmin;
+
+        let solved = Self::GMIN_STEPS
+            .iter()
+            .map(|&step| gmin + step)
+            .chain([gmin])
+            .try_for_each(|g| {
+                circuit.newton.gmin = g;
+                circuit.solve(Mode::DC_FULL)
+            });
+
+        // singular without a shunt, the solution with the smallest one stands
+        let solved = match solved {
+            Err(SolveError::Singular(_)) if circuit.newton.gmin == gmin => Ok(()),
+            solved => solved,
+        };
+
+        circuit.newton.gmin = gmin;
+        solved
+    }
+}
+
+/// Node voltages and component parameters at the operating point.
+#[derive(Debug, Clone)]
+pub struct OperatingPoint {
+    pub voltages: Vec<(String, c64)>,
+    pub components: Vec<ComponentParameters>,
+    pub convergence: Convergence,
+}
+
+impl OperatingPoint {
+    pub fn voltage(&self, net: &str) -> Option<c64> {
+        self.voltages
+            .iter()
+            .find(|(name, _)| name == net)
+            .map(|&(_, v)| v)
+    }
+
+    pub fn parameter(&self, component: &str, parameter: &str) -> Option<c64> {
+        let (_, values) = self
+            .components
+            .iter()
+            .find(|(name, _)| name.as_deref() == Some(component))?;
+
+        values
+            .iter()
+            .find(|&&(p, _)| p == parameter)
+            .map(|&(_, value)| value)
+    }
+
+    /// Nets as rows with their voltage `V`, followed by the components.
+    pub fn table(&self) -> String {
+        let mut headers = vec!["V".to_string()];
+        for (_, values) in &self.components {
+            for &(parameter, _) in values {
+                if !headers.iter().any(|h| h == parameter) {
+                    headers.push(parameter.to_string());
+                }
+            }
+        }
+
+        let nets = self
+            .voltages
+            .iter()
+            .map(|(net, v)| (Some(net.clone()), HashMap::from([("V".to_string(), *v)])));
+
+        let components = self.components.iter().map(|(name, values)| {
+            let values = values.iter().map(|&(p, z)| (p.to_string(), z)).collect();
+            (name.clone(), values)
+        });
+
+        print_table(headers, nets.chain(components).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CircuitBuilder, ComponentLibrary, Parser,
+        component::{Capacitor, DC1Source, Ground, Inductor, Resistor},
+    };
+
+    use super::*;
+
+    fn assert_close(z: c64, expected: f64) {
+        assert!((z.re - expected).abs() < 1e-9, "{} != {}", z.re, expected);
+        assert!(z.im.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_reactive_components_at_dc() {
+        let mut circuit = Circuit::new();
+        let [vin, mid, out, gnd] = ["in", "mid", "out", "gnd"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 6. }, Some("V1".into()), [vin]);
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            Some("R1".into()),
+            [vin, mid],
+        );
+        circuit.put(
+            Inductor { inductance_h: 1e-3 },
+            Some("L1".into()),
+            [mid, out],
+        );
+        circuit.put(
+            Resistor {
+                resistance_ohm: 2e3,
+            },
+            Some("R2".into()),
+            [out, gnd],
+        );
+        circuit.put(
+            Capacitor {
+                capacitance_f: 1e-6,
+            },
+            Some("C1".into()),
+            [out, gnd],
+        );
+        circuit.put(Ground, None, [gnd]);
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+
+        assert_close(op.voltage("mid").unwrap(), 4.);
+        assert_close(op.voltage("out").unwrap(), 4.);
+        assert_close(op.parameter("L1", "I").unwrap(), 2e-3);
+        assert_close(op.parameter("L1", "V").unwrap(), 0.);
+        assert_close(op.parameter("C1", "I").unwrap(), 0.);
+        assert_close(op.parameter("V1", "P").unwrap(), 12e-3);
+
+        let table = op.table();
+        assert!(table.contains("L1"));
+        assert!(table.contains("out"));
+
+        // a transient from the operating point stays there
+        circuit.step(1e-6).unwrap();
+        assert_close(circuit.voltage("out").unwrap(), 4.);
+        assert_close(circuit.parameter("L1", "I").unwrap(), 2e-3);
+    }
+
+    #[test]
+    fn test_convergence_aids() {
+        let mut builder = CircuitBuilder::new();
+        let netlist = "
+            dc-source-1-terminal in V=100
+            resistor in a R=1
+            diode a b
+            diode b c
+            npn c c gnd
+            ground gnd
+        ";
+        builder.add_commands(Parser::from(netlist).parse_commands().unwrap());
+        let library = ComponentLibrary::with_builtins();
+
+        let mut circuit = builder.build(&library).unwrap();
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        assert_eq!(op.convergence, Convergence::Newton);
+        let expected = op.voltage("a").unwrap().re;
+
+        // too few iterations to climb the three junctions from zero in one go
+        let mut circuit = builder.build(&library).unwrap();
+        circuit.newton.max_iterations = 5;
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        assert_eq!(op.convergence, Convergence::GminStepping);
+        assert!((op.voltage("a").unwrap().re - expected).abs() < 1e-3);
+        assert_eq!(circuit.newton.gmin, 0.);
+
+        let mut circuit = builder.build(&library).unwrap();
+        circuit.solve(Mode::Dc { source_scale: 0.5 }).unwrap();
+        assert!((circuit.voltage("in").unwrap().re - 50.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_source_stepping() {
+        let mut builder = CircuitBuilder::new();
+        let netlist = "
+            dc-source-1-terminal in V=10
+            resistor in a R=10
+            npn a a b
+            npn b b c
+            npn c c gnd
+            resistor in c R=100
+            ground gnd
+        ";
+        builder.add_commands(Parser::from(netlist).parse_commands().unwrap());
+        let library = ComponentLibrary::with_builtins();
+
+        let mut circuit = builder.build(&library).unwrap();
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        assert_eq!(op.convergence, Convergence::Newton);
+        let expected = op.voltage("a").unwrap().re;
+
+        // neither Newton–Raphson nor any of the Gmin steps converge in so few iterations
+        let mut circuit = builder.build(&library).unwrap();
+        circuit.newton.max_iterations = 9;
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        assert_eq!(op.convergence, Convergence::SourceStepping);
+        assert!((op.voltage("a").unwrap().re - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_floating_capacitor_node() {
+        let mut circuit = Circuit::new();
+        let [vin, mid, gnd] = ["in", "mid", "gnd"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 5. }, None, [vin]);
+        for nets in [[vin, mid], [mid, gnd]] {
+            circuit.put(
+                Capacitor {
+                    capacitance_f: 1e-6,
+                },
+                None,
+                nets,
+            );
+        }
+        circuit.put(Ground, None, [gnd]);
+
+        assert!(matches!(
+            circuit.solve(Mode::DC_FULL),
+            Err(SolveError::Singular(_))
+        ));
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        assert_eq!(op.convergence, Convergence::GminStepping);
+        assert_close(op.voltage("in").unwrap(), 5.);
+        assert_eq!(circuit.newton.gmin, 0.);
+    }
+}
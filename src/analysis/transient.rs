@@ -0,0 +1,485 @@
+use std::fmt::Write;
+
+use crate::{
+    analysis::{AnalysisError, Probe, find_column, record},
+    circuit::{Circuit, SolveError},
+    component::{Integration, Mode},
+    numerical::{Tolerance, c64},
+};
+
+/// How the transient driver picks its steps.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Timestep {
+    /// Always `step_s`, the last step shortened to land on `stop_s`.
+    #[default]
+    Fixed,
+    /// Starts at `step_s`, then grows or shrinks the step within the bounds to keep
+    /// the local truncation error within `tolerance`, retrying steps that exceed it.
+    Adaptive {
+        min_step_s: f64,
+        max_step_s: f64,
+        tolerance: Tolerance,
+    },
+}
+
+impl Timestep {
+    /// Whether the bounds are positive and finite, and `step_s` lies within them.
+    fn holds(&self, step_s: f64) -> bool {
+        match *self {
+            Timestep::Fixed => true,
+            Timestep::Adaptive {
+                min_step_s,
+                max_step_s,
+                ..
+            } => {
+                0. < min_step_s
+                    && min_step_s <= step_s
+                    && step_s <= max_step_s
+                    && max_step_s.is_finite()
+            }
+        }
+    }
+}
+
+/// How much to scale the step after one with the given [`Component::truncation_error`],
+/// aiming a little below the tolerance.
+///
+/// [`Component::truncation_error`]: crate::Component::truncation_error
+fn step_factor(error: f64, integration: Integration) -> f64 {
+    const SAFETY: f64 = 0.9;
+    const MAX_GROWTH: f64 = 2.;
+
+    if error == 0. {
+        return MAX_GROWTH;
+    }
+
+    let exponent = -1. / (integration.order() + 1) as f64;
+    (SAFETY * error.powf(exponent)).clamp(0.1, MAX_GROWTH)
+}
+
+/// Time-domain simulation from `t = 0` to `stop_s` with steps chosen by `timestep`,
+/// recording the probes at every accepted step from `start_s` on, and at `t = 0`
+/// itself when `start_s` is 0. Steps that pass
+/// a [`Component::breakpoint`] are cut short to end on it.
+///
+/// It starts from the state the components hold, like SPICE's `UIC`: the `t = 0` point
+/// is solved in [`Mode::Initial`], with capacitor voltages, inductor currents and the
+/// other reactive states held exactly there. Run a [`DcOperatingPoint`] first to start
+/// from rest instead.
+///
+/// [`DcOperatingPoint`]: crate::analysis::DcOperatingPoint
+///
+/// [`Component::breakpoint`]: crate::Component::breakpoint
+#[derive(Debug, Clone)]
+pub struct Transient {
+    pub start_s: f64,
+    pub stop_s: f64,
+    pub step_s: f64,
+    pub timestep: Timestep,
+    pub integration: Integration,
+    pub probes: Vec<Probe>,
+}
+
+impl Transient {
+    pub fn run(&self, circuit: &mut Circuit) -> Result<Waveform, AnalysisError> {
+        let spans = self.start_s <= self.stop_s && self.stop_s.is_finite();
+        if !spans || self.step_s.is_nan() || self.step_s <= 0. || self.step_s.is_infinite() {
+            return Err(AnalysisError::InvalidTimeSpan {
+                start_s: self.start_s,
+                stop_s: self.stop_s,
+                step_s: self.step_s,
+            });
+        }
+
+        if let Timestep::Adaptive {
+            min_step_s,
+            max_step_s,
+            ..
+        } = self.timestep
+            && !self.timestep.holds(self.step_s)
+        {
+            return Err(AnalysisError::InvalidTimestep {
+                min_step_s,
+                step_s: self.step_s,
+                max_step_s,
+            });
+        }
+
+        let mut waveform = Waveform {
+            time: vec![],
+            columns: self.probes.iter().map(|p| (p.clone(), vec![])).collect(),
+            rejected_steps: 0,
+        };
+
+        let mut t = 0.;
+        let mut step = self.step_s;
+        let mut breakpoint = None;
+        // the step taken before one was cut short for a breakpoint, to go on with after it
+        let mut resume = None;
+        // how close time points may get, to breakpoints and to `stop_s`
+        let resolution = self.step_s * 1e-9;
+
+        if self.start_s <= 0. {
+            circuit
+                .solve(Mode::Initial)
+                .map_err(|error| AnalysisError::at(0., error))?;
+
+            waveform.time.push(0.);
+            record(&mut waveform.columns, circuit)?;
+        }
+
+        // the last step is shortened to land on `stop_s` exactly
+        while self.stop_s - t > resolution {
+            let dt = breakpoint.take().unwrap_or(step).min(self.stop_s - t);
+            let mode = Mode::Transient {
+                dt,
+                integration: self.integration,
+            };
+
+            let solved = circuit.solve(mode);
+
+            if solved.is_ok()
+                && let Some(until) = circuit.breakpoint(mode)
+                && until < dt
+                && until > resolution
+            {
+                breakpoint = Some(until);
+                resume.get_or_insert(step);
+                continue;
+            }
+
+            if let Timestep::Adaptive {
+                min_step_s,
+                max_step_s,
+                tolerance,
+            } = self.timestep
+            {
+                // a much shorter step starts Newton–Raphson closer to its solution
+                if let Err(SolveError::NonConvergence { .. }) = solved
+                    && dt > min_step_s
+                {
+                    step = (dt / 8.).max(min_step_s);
+                    waveform.rejected_steps += 1;
+                    continue;
+                }
+
+                solved.map_err(|error| AnalysisError::at(t, error))?;
+
+                let error = circuit.truncation_error(mode, &tolerance).unwrap_or(0.);
+                step = (dt * step_factor(error, self.integration)).clamp(min_step_s, max_step_s);
+
+                if error > 1. {
+                    if dt <= min_step_s {
+                        return Err(AnalysisError::TimestepTooSmall {
+                            point: t,
+                            step_s: dt,
+                        });
+                    }
+
+                    waveform.rejected_steps += 1;
+                    continue;
+                }
+            } else {
+                solved.map_err(|error| AnalysisError::at(t, error))?;
+            }
+
+            circuit.post_stamp_all(mode);
+            t += dt;
+
+            if let Some(resume) = resume.take() {
+                step = step.max(resume);
+            }
+
+            if t >= self.start_s {
+                waveform.time.push(t);
+                record(&mut waveform.columns, circuit)?;
+            }
+        }
+
+        Ok(waveform)
+    }
+}
+
+/// Probe values against time, one column per probe.
+#[derive(Debug, Clone)]
+pub struct Waveform {
+    /// The accepted time points.
+    pub time: Vec<f64>,
+    pub columns: Vec<(Probe, Vec<c64>)>,
+    /// Steps retried with a smaller `dt` for exceeding the error tolerance.
+    pub rejected_steps: usize,
+}
+
+impl Waveform {
+    /// Looks up a column by the probe's printed name, e.g. `V(out)`.
+    pub fn column(&self, probe: &str) -> Option<&[c64]> {
+        find_column(&self.columns, probe)
+    }
+
+    /// The real, physical part of a column against time, ready for [`crate::print_chart`].
+    pub fn points(&self, probe: &str) -> Option<Vec<(f64, f64)>> {
+        let values = self.column(probe)?;
+        Some(
+            self.time
+                .iter()
+                .zip(values)
+                .map(|(&t, z)| (t, z.re))
+                .collect(),
+        )
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("t");
+
+        for (probe, _) in &self.columns {
+            write!(csv, ",{}", probe).unwrap();
+        }
+
+        for (i, t) in self.time.iter().enumerate() {
+            write!(csv, "\n{}", t).unwrap();
+
+            for (_, values) in &self.columns {
+                write!(csv, ",{}", values[i].re).unwrap();
+            }
+        }
+
+        csv.push('\n');
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::{Capacitor, DC1Source, Ground, Inductor, Resistor};
+
+    use super::*;
+
+    #[test]
+    fn test_rc_charging() {
+        let mut circuit = Circuit::new();
+        let [vin, out, gnd] = ["in", "out", "gnd"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 1. }, None, [vin]);
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            None,
+            [vin, out],
+        );
+        circuit.put(
+            Capacitor {
+                capacitance_f: 1e-6,
+            },
+            Some("C1".into()),
+            [out, gnd],
+        );
+        circuit.put(Ground, None, [gnd]);
+
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: 5e-3,
+            step_s: 1e-6,
+            timestep: Timestep::Fixed,
+            integration: Integration::BackwardEuler,
+            probes: vec![Probe::voltage("out"), Probe::parameter("C1", "I")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+
+        assert_eq!(waveform.time.len(), 5001);
+        assert!((waveform.time.last().unwrap() - 5e-3).abs() < 1e-12);
+
+        // it starts uncharged, held there with the whole source across the resistor
+        assert_eq!(waveform.time[0], 0.);
+        assert!(waveform.column("V(out)").unwrap()[0].norm() < 1e-12);
+
+        let i = waveform.column("I(C1)").unwrap()[0].re;
+        assert!((i - 1e-3).abs() < 1e-12, "{}", i);
+
+        // one time constant in, the capacitor sits at 1 - 1/e
+        let tau = waveform.column("V(out)").unwrap()[1000].re;
+        assert!((tau - (1. - (-1f64).exp())).abs() < 1e-3, "{}", tau);
+
+        let i = waveform.column("I(C1)").unwrap()[1000].re;
+        assert!((i - (1. - tau) / 1e3).abs() < 1e-6, "{}", i);
+
+        let csv = waveform.to_csv();
+        assert!(csv.starts_with("t,V(out),I(C1)\n"));
+        assert_eq!(csv.lines().count(), 5002);
+    }
+
+    #[test]
+    fn test_invalid_time_span() {
+        let mut circuit = Circuit::new();
+
+        for (start_s, stop_s, step_s) in [
+            (0., 1., 0.),
+            (0., 1., -1e-3),
+            (0., 1., f64::NAN),
+            (0., 1., f64::INFINITY),
+            (1., 0., 1e-3),
+            (0., f64::INFINITY, 1e-3),
+            (f64::NAN, 1., 1e-3),
+        ] {
+            let transient = Transient {
+                start_s,
+                stop_s,
+                step_s,
+                timestep: Timestep::Fixed,
+                integration: Integration::default(),
+                probes: vec![],
+            };
+
+            assert!(matches!(
+                transient.run(&mut circuit),
+                Err(AnalysisError::InvalidTimeSpan { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_invalid_timestep() {
+        let mut circuit = Circuit::new();
+
+        for (min_step_s, step_s, max_step_s) in [
+            (1e-3, 1e-6, 1e-9),
+            (0., 1e-6, 1e-3),
+            (1e-9, 1e-2, 1e-3),
+            (1e-9, 1e-6, f64::INFINITY),
+            (f64::NAN, 1e-6, 1e-3),
+            (1e-9, 1e-6, f64::NAN),
+        ] {
+            let transient = Transient {
+                start_s: 0.,
+                stop_s: 1.,
+                step_s,
+                timestep: Timestep::Adaptive {
+                    min_step_s,
+                    max_step_s,
+                    tolerance: Tolerance::default(),
+                },
+                integration: Integration::default(),
+                probes: vec![],
+            };
+
+            assert!(matches!(
+                transient.run(&mut circuit),
+                Err(AnalysisError::InvalidTimestep { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_unknown_probe() {
+        let mut circuit = Circuit::new();
+        let a = circuit.net("a");
+        circuit.put(Ground, None, [a]);
+
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: 1.,
+            step_s: 0.5,
+            timestep: Timestep::default(),
+            integration: Integration::default(),
+            probes: vec![Probe::voltage("b")],
+        };
+
+        assert!(matches!(
+            transient.run(&mut circuit),
+            Err(AnalysisError::UnknownProbe { .. })
+        ));
+    }
+
+    /// Peak capacitor voltage over the last of five periods of an undamped LC tank
+    /// kicked by a 1V step, which swings between 0 and 2V when energy is conserved.
+    fn lc_tank_peak(integration: Integration) -> f64 {
+        let mut circuit = Circuit::new();
+        let [vin, out, gnd] = ["in", "out", "gnd"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 1. }, None, [vin]);
+        circuit.put(Ground, None, [gnd]);
+        circuit.put(Inductor { inductance_h: 1e-3 }, None, [vin, out]);
+        circuit.put(
+            Capacitor {
+                capacitance_f: 1e-6,
+            },
+            None,
+            [out, gnd],
+        );
+
+        let period = 2. * std::f64::consts::PI * (1e-3f64 * 1e-6).sqrt();
+        let transient = Transient {
+            start_s: 4. * period,
+            stop_s: 5. * period,
+            step_s: period / 100.,
+            timestep: Timestep::Fixed,
+            integration,
+            probes: vec![Probe::voltage("out")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+        let values = waveform.column("V(out)").unwrap();
+        values.iter().map(|z| z.re).fold(f64::MIN, f64::max)
+    }
+
+    #[test]
+    fn test_integration_damping() {
+        let euler = lc_tank_peak(Integration::BackwardEuler);
+        let gear = lc_tank_peak(Integration::Gear2);
+        let trapezoidal = lc_tank_peak(Integration::Trapezoidal);
+
+        assert!(euler < 1.5, "{}", euler);
+        assert!(gear > 1.9 && gear < trapezoidal, "{}", gear);
+        assert!((trapezoidal - 2.).abs() < 1e-2, "{}", trapezoidal);
+    }
+
+    #[test]
+    fn test_adaptive_timestep() {
+        let mut circuit = Circuit::new();
+        let [vin, out, gnd] = ["in", "out", "gnd"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 1. }, None, [vin]);
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            None,
+            [vin, out],
+        );
+        circuit.put(
+            Capacitor {
+                capacitance_f: 1e-6,
+            },
+            None,
+            [out, gnd],
+        );
+        circuit.put(Ground, None, [gnd]);
+
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: 10e-3,
+            step_s: 1e-6,
+            timestep: Timestep::Adaptive {
+                min_step_s: 1e-9,
+                max_step_s: 1e-3,
+                tolerance: Tolerance::default(),
+            },
+            integration: Integration::Trapezoidal,
+            probes: vec![Probe::voltage("out")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+        let time = &waveform.time;
+
+        assert!(time.len() < 1000, "{}", time.len());
+        assert!((time.last().unwrap() - 10e-3).abs() < 1e-12);
+        // the steps grow as the capacitor settles
+        assert!(time[time.len() - 2] - time[time.len() - 3] > 10. * (time[1] - time[0]));
+
+        for (t, v) in time.iter().zip(waveform.column("V(out)").unwrap()) {
+            let expected = 1. - (-t / 1e-3).exp();
+            assert!((v.re - expected).abs() < 1e-2, "{} at {}", v.re, t);
+        }
+    }
+}
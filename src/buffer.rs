@@ -12,7 +12,10 @@ pub struct ComponentBuffer {
 
 #[derive(Debug, Clone, Copy, Zeroable)]
 #[repr(C)]
-struct ComponentStoredData<C: Component> {
+struct ComponentStoredData<C>
+where
+    C: Component,
+{
     component: C,
     state: C::State,
 }
@@ -24,7 +27,7 @@ impl ComponentBuffer {
         let alignment = align_of::<ComponentStoredData<C>>();
         let size = size_of::<ComponentStoredData<C>>().max(1);
 
-        let stride = ((size + alignment - 1) / alignment) * alignment;
+        let stride = size.div_ceil(alignment) * alignment;
         let type_id = TypeId::of::<C>();
 
         Self {
@@ -38,6 +41,10 @@ impl ComponentBuffer {
         self.buffer.len() / self.stride
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
     pub fn push<C: Component>(&mut self, component: C) {
         let data = ComponentStoredData {
             component,
@@ -67,6 +74,15 @@ impl ComponentBuffer {
             _phantom: PhantomData,
         }
     }
+
+    pub fn get_mut<C: Component>(&mut self, idx: usize) -> Option<(&mut C, &mut C::State)> {
+        ComponentIteratorMut {
+            idx,
+            buffer: self,
+            _phantom: PhantomData,
+        }
+        .next()
+    }
 }
 
 struct ComponentIterator<'buffer, C: Component> {
@@ -90,7 +106,7 @@ impl<'buffer, C: Component> Iterator for ComponentIterator<'buffer, C> {
         }
 
         let start = self.idx * self.buffer.stride;
-        let end = start + size_of::<C>();
+        let end = start + size_of::<ComponentStoredData<C>>();
 
         self.idx += 1;
 
@@ -131,11 +147,68 @@ impl<'buffer, C: Component> Iterator for ComponentIteratorMut<'buffer, C> {
     }
 }
 
+/// Where a component's variable-length data sits in [`Tables`]. Being `Pod`,
+/// it can be stored in the component itself.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct Table {
+    start: u32,
+    len: u32,
+}
+
+impl Table {
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Side storage for data that doesn't fit a `Pod` component, such as stimulus tables.
+/// Components keep a [`Table`] into it and read it in [`Component::load`], so that
+/// the typed iteration over the component buffers stays as it is.
+#[derive(Debug, Clone, Default)]
+pub struct Tables {
+    values: Vec<f64>,
+}
+
+impl Tables {
+    pub fn push(&mut self, values: impl IntoIterator<Item = f64>) -> Table {
+        let start = self.values.len();
+        self.values.extend(values);
+
+        Table {
+            start: start as u32,
+            len: (self.values.len() - start) as u32,
+        }
+    }
+
+    pub fn get(&self, table: Table) -> &[f64] {
+        let start = table.start as usize;
+        &self.values[start..start + table.len()]
+    }
+
+    /// Runs `f`, dropping whatever it pushed if it fails, so that a component
+    /// that isn't built leaves nothing behind.
+    pub fn transaction<T, E>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, E>) -> Result<T, E> {
+        let len = self.values.len();
+        let result = f(self);
+
+        if result.is_err() {
+            self.values.truncate(len);
+        }
+
+        result
+    }
+}
+
 #[cfg(test)]
 mod test {
     use bytemuck::{Pod, Zeroable};
 
-    use crate::numerical::LinearEquations;
+    use crate::{component::Mode, numerical::LinearEquations};
 
     use super::*;
 
@@ -153,7 +226,7 @@ mod test {
         fn stamp(
             &self,
             _net: &mut LinearEquations,
-            _dt: f64,
+            _mode: Mode,
             _terminals: [u32; Self::TERMINAL_COUNT],
             _state: &Self::State,
         ) {
@@ -174,6 +247,19 @@ mod test {
         assert_eq!(n, N);
     }
 
+    #[test]
+    fn test_tables() {
+        let mut tables = Tables::default();
+
+        let a = tables.push([1., 2., 3.]);
+        let empty = tables.push([]);
+        let b = tables.push(vec![4.; 2]);
+
+        assert_eq!(tables.get(a), &[1., 2., 3.]);
+        assert!(empty.is_empty() && tables.get(empty).is_empty());
+        assert_eq!(tables.get(b), &[4., 4.]);
+    }
+
     #[test]
     fn test_component_mut_iteration() {
         let mut buffer = ComponentBuffer::new::<DemoComponent>();
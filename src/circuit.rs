@@ -1,67 +1,468 @@
 use std::{any::TypeId, collections::HashMap};
 
-use crate::{buffer::ComponentBuffer, component::Component, numerical::LinearEquations};
+use crate::{
+    buffer::{ComponentBuffer, Tables},
+    component::{BehavioralSource, Component, Integration, Linearization, Mode, Quantity},
+    numerical::{LinearEquations, SingularMatrix, Tolerance, c64},
+};
+
+type StampAllFn = dyn Fn(&ComponentBuffer, &mut LinearEquations, Mode, &[u32]);
+type PostStampAllFn = dyn Fn(&mut ComponentBuffer, &LinearEquations, Mode, &[u32], &Tables);
+type LimitAllFn = dyn Fn(&mut ComponentBuffer, &LinearEquations, Mode, &[u32], &Tolerance) -> bool;
+type TruncationErrorFn =
+    dyn Fn(&ComponentBuffer, &LinearEquations, Mode, &[u32], &Tolerance) -> Option<f64>;
+type BreakpointFn = dyn Fn(&ComponentBuffer, &LinearEquations, Mode, &[u32]) -> Option<f64>;
+type ParameterFn = dyn Fn(&ComponentBuffer, &LinearEquations, &[u32], usize, &str) -> Option<c64>;
+
+/// A component's name with the values of its [`Component::PARAMETERS`].
+pub type ComponentParameters = (Option<String>, Vec<(&'static str, c64)>);
 
 struct Components {
     buffer: ComponentBuffer,
     terminals: Vec<u32>,
-    stamp_all_fn: Box<dyn Fn(&ComponentBuffer, &mut LinearEquations, f64, &[u32])>,
-    post_stamp_all_fn: Box<dyn Fn(&mut ComponentBuffer, &LinearEquations, f64, &[u32])>,
+    stamp_all_fn: Box<StampAllFn>,
+    post_stamp_all_fn: Box<PostStampAllFn>,
+    limit_all_fn: Box<LimitAllFn>,
+    truncation_error_fn: Box<TruncationErrorFn>,
+    breakpoint_fn: Box<BreakpointFn>,
+    parameter_fn: Box<ParameterFn>,
+    parameters: &'static [&'static str],
+    terminal_count: usize,
+    current_branch: Option<usize>,
+    priority: usize,
+    nonlinear: bool,
+    nonlinear_at_dc: bool,
+}
+
+impl Components {
+    /// Whether they have to be iterated on in `mode`, see [`Component::NONLINEAR`].
+    fn is_nonlinear(&self, mode: Mode) -> bool {
+        self.nonlinear || self.nonlinear_at_dc && matches!(mode, Mode::Dc { .. })
+    }
+}
+
+/// The parameters a [`BehavioralSource`] reports.
+const BEHAVIORAL_PARAMETERS: &[&str] = &["V", "I", "P", "t"];
+
+/// A [`BehavioralSource`] put between `nets`, a voltage with its current as the unknown
+/// `branch`, with the time it is at and where it was linearized last.
+struct Behavioral {
+    source: BehavioralSource,
+    name: Option<String>,
+    nets: [u32; 2],
+    branch: Option<u32>,
+    t: f64,
+    /// What each of the variables of the source refers to, once the circuit is resolved.
+    references: Vec<Option<Reference>>,
+    /// The values of the variables and the slopes by each, kept to linearize without allocating.
+    values: Vec<c64>,
+    slopes: Vec<f64>,
+    linearization: Linearization,
+}
+
+impl Behavioral {
+    /// Its `parameter` with the unknowns taken from `le`, where it is at `value`.
+    fn parameter(&self, le: &LinearEquations, value: f64, parameter: &str) -> Option<c64> {
+        if parameter == "t" {
+            return Some(c64::real(self.t));
+        }
+
+        let [p, n] = self.nets;
+        let branch = self.branch.map(|k| le.get_branch_current(k));
+        let across = le.get_voltage_across(p, n);
+
+        self.source.parameter(value, across, branch, parameter)
+    }
+}
+
+/// What a variable of a [`BehavioralSource`] refers to.
+enum Reference {
+    Time,
+    /// The voltage of a net.
+    Net(u32),
+    /// A parameter of the component at `idx` among those of its type, which depends
+    /// on its terminals.
+    Parameter {
+        type_id: TypeId,
+        idx: u32,
+        parameter: String,
+    },
+}
+
+/// Where the component with a name is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Component(TypeId, u32),
+    Behavioral(usize),
+}
+
+/// What the circuit can't resolve in the expression of a [`BehavioralSource`],
+/// called `name` if it has one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unresolved {
+    /// A variable refers to nothing in the circuit.
+    Variable {
+        name: Option<String>,
+        variable: String,
+    },
+    /// A variable refers to a parameter of another behavioral source, which isn't known
+    /// until that one is linearized.
+    Behavioral {
+        name: Option<String>,
+        variable: String,
+    },
+    /// It calls a function that doesn't exist or doesn't take that many arguments.
+    Function {
+        name: Option<String>,
+        function: String,
+        arguments: usize,
+    },
+}
+
+/// Settings of the Newton–Raphson loop that solves circuits with
+/// [`Component::NONLINEAR`] components.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Newton {
+    pub max_iterations: usize,
+    /// How little the unknowns have to change between iterations to be converged.
+    pub tolerance: Tolerance,
+    /// Conductance from every node to ground, raised by Gmin stepping to ease convergence.
+    pub gmin: f64,
+}
+
+impl Default for Newton {
+    fn default() -> Self {
+        Self {
+            max_iterations: 100,
+            tolerance: Tolerance::default(),
+            gmin: 0.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveError {
+    Singular(SingularMatrix),
+    /// The Newton–Raphson loop was still moving after `iterations`.
+    NonConvergence {
+        iterations: usize,
+    },
+    /// The behavioral source called `name` has no real, finite value or slope at the
+    /// solution it is linearized around, like `sqrt(V_in)` with `V_in` negative.
+    Undefined {
+        name: Option<String>,
+    },
+}
+
+impl From<SingularMatrix> for SolveError {
+    fn from(error: SingularMatrix) -> Self {
+        SolveError::Singular(error)
+    }
 }
 
 pub struct Circuit {
     names: HashMap<(TypeId, u32), String>,
+    /// The components and behavioral sources by name.
+    locations: HashMap<String, Location>,
+    nets: HashMap<String, u32>,
+    unknowns: u32,
+    /// Whether each unknown is a branch current rather than a node voltage,
+    /// as far as the last branch allocated.
+    branches: Vec<bool>,
     circuit: HashMap<TypeId, Components>,
+    /// The component types by increasing [`Component::PRIORITY`], the order they are stamped in.
+    order: Vec<TypeId>,
+    behavioral: Vec<Behavioral>,
+    /// Whether the variables of the behavioral sources are resolved against what is in the circuit.
+    resolved: bool,
+    /// A copy of the solution to nudge when differentiating parameters of components.
+    perturbed: LinearEquations,
     pub equations: LinearEquations,
+    /// Variable-length component data, see [`Component::load`].
+    pub tables: Tables,
+    pub newton: Newton,
+}
+
+impl Default for Circuit {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Circuit {
     pub fn new() -> Self {
         Self {
             circuit: Default::default(),
+            order: vec![],
+            behavioral: vec![],
+            resolved: true,
+            perturbed: LinearEquations::default(),
             equations: LinearEquations::default(),
+            tables: Tables::default(),
             names: Default::default(),
+            locations: Default::default(),
+            nets: Default::default(),
+            unknowns: 0,
+            branches: vec![],
+            newton: Newton::default(),
         }
     }
 
+    /// Returns the node index of the net called `name`,
+    /// assigning the next free unknown if the net is new.
+    pub fn net(&mut self, name: &str) -> u32 {
+        if let Some(&idx) = self.nets.get(name) {
+            return idx;
+        }
+
+        let idx = self.allocate_unknown();
+        self.nets.insert(name.to_owned(), idx);
+        idx
+    }
+
+    fn allocate_unknown(&mut self) -> u32 {
+        let idx = self.unknowns;
+        self.unknowns += 1;
+        idx
+    }
+
+    fn allocate_branch(&mut self) -> u32 {
+        let idx = self.allocate_unknown();
+        self.branches.resize(idx as usize, false);
+        self.branches.push(true);
+        idx
+    }
+
+    fn is_branch(&self, idx: usize) -> bool {
+        self.branches.get(idx).copied().unwrap_or(false)
+    }
+
+    pub fn net_index(&self, name: &str) -> Option<u32> {
+        self.nets.get(name).copied()
+    }
+
+    pub fn nets(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.nets.iter().map(|(name, &idx)| (name.as_str(), idx))
+    }
+
+    pub fn voltage(&self, net: &str) -> Option<c64> {
+        let idx = self.net_index(net)?;
+        self.equations.x.get(idx as usize).copied()
+    }
+
+    /// Looks up a parameter of the component called `component`, see [`Component::parameter`].
+    pub fn parameter(&self, component: &str, parameter: &str) -> Option<c64> {
+        match *self.locations.get(component)? {
+            Location::Component(type_id, idx) => {
+                self.component_parameter(&self.equations, type_id, idx, parameter)
+            }
+            Location::Behavioral(idx) => {
+                let behavioral = &self.behavioral[idx];
+                let value = self.behavioral_value(behavioral)?;
+                behavioral.parameter(&self.equations, value, parameter)
+            }
+        }
+    }
+
+    /// The component called `name`, if it is a `C`.
+    pub fn component<C: Component>(&self, name: &str) -> Option<&C> {
+        let Location::Component(type_id, idx) = *self.locations.get(name)? else {
+            return None;
+        };
+
+        if type_id != TypeId::of::<C>() {
+            return None;
+        }
+
+        let (component, _) = self.circuit[&type_id]
+            .buffer
+            .iter::<C>()
+            .nth(idx as usize)?;
+        Some(component)
+    }
+
+    /// `parameter` of the component at `idx` among those of type `type_id`, with the
+    /// unknowns taken from `le`.
+    fn component_parameter(
+        &self,
+        le: &LinearEquations,
+        type_id: TypeId,
+        idx: u32,
+        parameter: &str,
+    ) -> Option<c64> {
+        let components = &self.circuit[&type_id];
+
+        (components.parameter_fn)(
+            &components.buffer,
+            le,
+            &components.terminals[..],
+            idx as usize,
+            parameter,
+        )
+    }
+
+    /// Every component with the values of all its [`Component::PARAMETERS`],
+    /// named components first and sorted by name.
+    pub fn components(&self) -> Vec<ComponentParameters> {
+        let mut components: Vec<_> = self
+            .circuit
+            .iter()
+            .flat_map(|(&type_id, components)| {
+                (0..components.buffer.len()).map(move |idx| {
+                    let values = components
+                        .parameters
+                        .iter()
+                        .filter_map(|&parameter| {
+                            let value = (components.parameter_fn)(
+                                &components.buffer,
+                                &self.equations,
+                                &components.terminals[..],
+                                idx,
+                                parameter,
+                            )?;
+                            Some((parameter, value))
+                        })
+                        .collect();
+
+                    (self.names.get(&(type_id, idx as u32)).cloned(), values)
+                })
+            })
+            .collect();
+
+        components.extend(self.behavioral.iter().map(|behavioral| {
+            let values = BEHAVIORAL_PARAMETERS
+                .iter()
+                .filter_map(|&parameter| {
+                    let value = self.behavioral_value(behavioral)?;
+                    Some((
+                        parameter,
+                        behavioral.parameter(&self.equations, value, parameter)?,
+                    ))
+                })
+                .collect();
+
+            (behavioral.name.clone(), values)
+        }));
+
+        components.sort_by(|(a, _), (b, _)| (a.is_none(), a).cmp(&(b.is_none(), b)));
+        components
+    }
+
+    /// Puts a component between the given nets. The circuit allocates
+    /// the [`Component::BRANCH_COUNT`] branch unknowns itself. Names have to be
+    /// unique among components and behavioral sources alike.
     pub fn put<C: Component>(
         &mut self,
         component: C,
         name: Option<String>,
-        terminals: [u32; C::TERMINAL_COUNT],
-    ) {
+        nets: [u32; C::TERMINAL_COUNT - C::BRANCH_COUNT],
+    ) where
+        [(); C::TERMINAL_COUNT]:,
+    {
         let type_id = TypeId::of::<C>();
 
+        if let Some(&max) = nets.iter().max() {
+            self.unknowns = self.unknowns.max(max + 1);
+        }
+
+        let mut terminals = [0; C::TERMINAL_COUNT];
+        terminals[..nets.len()].copy_from_slice(&nets);
+        for terminal in &mut terminals[nets.len()..] {
+            *terminal = self.allocate_branch();
+        }
+
+        // every node has a diagonal entry for the Gmin shunt
         self.equations.add_coordinates(
             C::ACTIVE_TERMINALS
                 .iter()
                 .copied()
-                .map(|(i, j)| (terminals[i], terminals[j])),
+                .map(|(i, j)| (terminals[i], terminals[j]))
+                .chain(nets.iter().map(|&net| (net, net))),
         );
 
+        if !self.circuit.contains_key(&type_id) {
+            let at = self
+                .order
+                .partition_point(|other| self.circuit[other].priority <= C::PRIORITY);
+            self.order.insert(at, type_id);
+        }
+
         let components = self.circuit.entry(type_id).or_insert_with(|| Components {
             buffer: ComponentBuffer::new::<C>(),
             terminals: vec![],
-            stamp_all_fn: Box::new(|components, le, dt, terminals| {
+            stamp_all_fn: Box::new(|components, le, mode, terminals| {
                 components
                     .iter::<C>()
                     .enumerate()
                     .for_each(|(i, (c, state))| {
                         let start = C::TERMINAL_COUNT * i;
                         let end = C::TERMINAL_COUNT * (i + 1);
-                        c.stamp(le, dt, terminals[start..end].try_into().unwrap(), state);
+                        c.stamp(le, mode, terminals[start..end].try_into().unwrap(), state);
                     });
             }),
-            post_stamp_all_fn: Box::new(|components, le, dt, terminals| {
+            post_stamp_all_fn: Box::new(|components, le, mode, terminals, tables| {
                 components
                     .iter_mut::<C>()
                     .enumerate()
                     .for_each(|(i, (c, state))| {
                         let start = C::TERMINAL_COUNT * i;
                         let end = C::TERMINAL_COUNT * (i + 1);
-                        c.post_stamp(le, dt, terminals[start..end].try_into().unwrap(), state);
+                        c.post_stamp(le, mode, terminals[start..end].try_into().unwrap(), state);
+                        c.load(tables, state);
                     });
             }),
+            limit_all_fn: Box::new(|components, le, mode, terminals, tolerance| {
+                components
+                    .iter_mut::<C>()
+                    .enumerate()
+                    .fold(false, |limited, (i, (c, state))| {
+                        let start = C::TERMINAL_COUNT * i;
+                        let end = C::TERMINAL_COUNT * (i + 1);
+                        let terminals = terminals[start..end].try_into().unwrap();
+                        c.limit(le, mode, terminals, state, tolerance) || limited
+                    })
+            }),
+            truncation_error_fn: Box::new(|components, le, mode, terminals, tolerance| {
+                components
+                    .iter::<C>()
+                    .enumerate()
+                    .filter_map(|(i, (c, state))| {
+                        let start = C::TERMINAL_COUNT * i;
+                        let end = C::TERMINAL_COUNT * (i + 1);
+                        let terminals = terminals[start..end].try_into().unwrap();
+                        c.truncation_error(le, mode, terminals, state, tolerance)
+                    })
+                    .reduce(f64::max)
+            }),
+            breakpoint_fn: Box::new(|components, le, mode, terminals| {
+                components
+                    .iter::<C>()
+                    .enumerate()
+                    .filter_map(|(i, (c, state))| {
+                        let start = C::TERMINAL_COUNT * i;
+                        let end = C::TERMINAL_COUNT * (i + 1);
+                        let terminals = terminals[start..end].try_into().unwrap();
+                        c.breakpoint(le, mode, terminals, state)
+                    })
+                    .reduce(f64::min)
+            }),
+            parameter_fn: Box::new(|components, le, terminals, idx, parameter| {
+                let (c, state) = components.iter::<C>().nth(idx)?;
+                let start = C::TERMINAL_COUNT * idx;
+                let end = C::TERMINAL_COUNT * (idx + 1);
+                c.parameter(
+                    le,
+                    terminals[start..end].try_into().unwrap(),
+                    state,
+                    parameter,
+                )
+            }),
+            parameters: C::PARAMETERS,
+            terminal_count: C::TERMINAL_COUNT,
+            current_branch: C::CURRENT_BRANCH,
+            priority: C::PRIORITY,
+            nonlinear: C::NONLINEAR,
+            nonlinear_at_dc: C::NONLINEAR_AT_DC,
         });
 
         let idx = components.buffer.len() as u32;
@@ -69,32 +470,761 @@ impl Circuit {
         components.buffer.push(component);
         components.terminals.extend_from_slice(&terminals);
 
+        if let Some((c, state)) = components.buffer.get_mut::<C>(idx as usize) {
+            c.load(&self.tables, state);
+        }
+
         if let Some(name) = name {
+            self.locations
+                .insert(name.clone(), Location::Component(type_id, idx));
             self.names.insert((type_id, idx), name);
         }
+
+        self.resolved = false;
+    }
+
+    /// Puts a [`BehavioralSource`] from `nets[0]` to `nets[1]`, allocating the branch
+    /// unknown of a voltage.
+    pub fn put_behavioral(
+        &mut self,
+        source: BehavioralSource,
+        name: Option<String>,
+        nets: [u32; 2],
+    ) {
+        if let Some(&max) = nets.iter().max() {
+            self.unknowns = self.unknowns.max(max + 1);
+        }
+
+        let branch = (source.quantity == Quantity::Voltage).then(|| self.allocate_branch());
+
+        self.equations
+            .add_coordinates(source.coordinates(nets, branch, []));
+
+        if let Some(name) = &name {
+            self.locations
+                .insert(name.clone(), Location::Behavioral(self.behavioral.len()));
+        }
+
+        self.behavioral.push(Behavioral {
+            source,
+            name,
+            nets,
+            branch,
+            t: 0.,
+            references: vec![],
+            values: vec![],
+            slopes: vec![],
+            linearization: Linearization::default(),
+        });
+        self.resolved = false;
+    }
+
+    /// The terminals of the component at `idx` among those of type `type_id`,
+    /// branch unknowns included.
+    fn terminals_of(&self, type_id: TypeId, idx: u32) -> &[u32] {
+        let components = &self.circuit[&type_id];
+        let start = components.terminal_count * idx as usize;
+
+        &components.terminals[start..start + components.terminal_count]
+    }
+
+    /// The branch unknown carrying the current through the component called `component`,
+    /// see [`Component::CURRENT_BRANCH`]. A behavioral voltage source has one too.
+    pub fn branch_current_of(&self, component: &str) -> Option<u32> {
+        match *self.locations.get(component)? {
+            Location::Component(type_id, idx) => {
+                let current_branch = self.circuit[&type_id].current_branch?;
+                Some(self.terminals_of(type_id, idx)[current_branch])
+            }
+            Location::Behavioral(idx) => self.behavioral[idx].branch,
+        }
     }
 
-    pub fn stamp_all(&mut self, dt: f64) {
-        for (_, component) in &mut self.circuit {
+    /// What the variable `name` with `subscript` of the [`BehavioralSource`] called `source`
+    /// refers to: `t`, `V_net` for a net, or a parameter of a component like `I_R1`.
+    fn resolve(
+        &self,
+        source: &Option<String>,
+        name: &str,
+        subscript: Option<&str>,
+    ) -> Result<Reference, Unresolved> {
+        let variable = || match subscript {
+            Some(subscript) => format!("{name}_{subscript}"),
+            None => name.to_string(),
+        };
+
+        match (name, subscript) {
+            ("t", None) => Ok(Reference::Time),
+            ("V", Some(net)) if self.nets.contains_key(net) => Ok(Reference::Net(self.nets[net])),
+            (parameter, Some(component)) => match self.locations.get(component) {
+                Some(&Location::Component(type_id, idx)) => Ok(Reference::Parameter {
+                    type_id,
+                    idx,
+                    parameter: parameter.to_string(),
+                }),
+                Some(Location::Behavioral(_)) => Err(Unresolved::Behavioral {
+                    name: source.clone(),
+                    variable: variable(),
+                }),
+                None => Err(Unresolved::Variable {
+                    name: source.clone(),
+                    variable: variable(),
+                }),
+            },
+            _ => Err(Unresolved::Variable {
+                name: source.clone(),
+                variable: variable(),
+            }),
+        }
+    }
+
+    /// Resolves the variables of every [`BehavioralSource`] and makes room in the matrix
+    /// for the unknowns they depend on, once all components are in. Returns variables that
+    /// refer to nothing in the circuit or to another behavioral source, which are taken
+    /// as zero, and calls of functions that don't exist.
+    pub fn resolve_behavioral(&mut self) -> Vec<Unresolved> {
+        let mut unresolved = vec![];
+        let mut coordinates = vec![];
+
+        for idx in 0..self.behavioral.len() {
+            let behavioral = &self.behavioral[idx];
+
+            if let Some((function, arguments)) = behavioral.source.expression.unknown_function() {
+                unresolved.push(Unresolved::Function {
+                    name: behavioral.name.clone(),
+                    function: function.to_string(),
+                    arguments,
+                });
+            }
+
+            let references: Vec<_> = behavioral
+                .source
+                .variables()
+                .map(|(name, subscript)| {
+                    self.resolve(&behavioral.name, name, subscript)
+                        .map_err(|error| unresolved.push(error))
+                        .ok()
+                })
+                .collect();
+
+            let dependencies = references.iter().flat_map(|reference| match reference {
+                Some(Reference::Net(net)) => std::slice::from_ref(net),
+                Some(Reference::Parameter { type_id, idx, .. }) => {
+                    self.terminals_of(*type_id, *idx)
+                }
+                Some(Reference::Time) | None => &[],
+            });
+            let linearization = Linearization::depending_on(dependencies.copied());
+
+            coordinates.extend(behavioral.source.coordinates(
+                behavioral.nets,
+                behavioral.branch,
+                linearization.jacobian.iter().map(|&(j, _)| j),
+            ));
+
+            let behavioral = &mut self.behavioral[idx];
+            behavioral.values = vec![c64::ZERO; references.len()];
+            behavioral.slopes = vec![0.; references.len()];
+            behavioral.references = references;
+            behavioral.linearization = linearization;
+        }
+
+        coordinates.retain(|coordinate| !self.equations.value_map.contains_key(coordinate));
+        if !coordinates.is_empty() {
+            self.equations.add_coordinates(coordinates);
+        }
+
+        self.resolved = true;
+        unresolved
+    }
+
+    /// Linearizes every [`BehavioralSource`] around the present solution, at the end of
+    /// a transient step. The parameters of components are differentiated by their terminals
+    /// on a copy of the solution.
+    fn linearize_behavioral(&mut self, mode: Mode) -> Result<(), SolveError> {
+        if self.behavioral.is_empty() {
+            return Ok(());
+        }
+
+        let mut behavioral = std::mem::take(&mut self.behavioral);
+        let mut perturbed = std::mem::take(&mut self.perturbed);
+        perturbed.x.clone_from(&self.equations.x);
+        let mut undefined = None;
+
+        for behavioral in &mut behavioral {
+            let t = match mode {
+                Mode::Transient { dt, .. } => behavioral.t + dt,
+                _ => behavioral.t,
+            };
+
+            for (value, reference) in behavioral.values.iter_mut().zip(&behavioral.references) {
+                *value = reference
+                    .as_ref()
+                    .and_then(|reference| self.reference_value(&self.equations, reference, t))
+                    .unwrap_or_default();
+            }
+
+            let linearization = &mut behavioral.linearization;
+            linearization.reset();
+
+            let Some(value) = behavioral
+                .source
+                .evaluate(&behavioral.values, &mut behavioral.slopes)
+            else {
+                undefined = Some(behavioral.name.clone());
+                break;
+            };
+            linearization.value = value;
+
+            for ((reference, &slope), &at) in behavioral
+                .references
+                .iter()
+                .zip(&behavioral.slopes)
+                .zip(&behavioral.values)
+            {
+                match reference {
+                    Some(Reference::Net(net)) => linearization.add(*net, slope),
+                    Some(reference @ Reference::Parameter { type_id, idx, .. }) => {
+                        // how the parameter moves with each of the terminals
+                        for &j in self.terminals_of(*type_id, *idx) {
+                            let x = self.equations.x[j as usize];
+                            let h = 1e-6 * (1. + x.norm());
+
+                            perturbed.x[j as usize] = x + c64::real(h);
+                            let nudged = self
+                                .reference_value(&perturbed, reference, t)
+                                .unwrap_or_default();
+                            perturbed.x[j as usize] = x;
+
+                            linearization.add(j, slope * (nudged - at).re / h);
+                        }
+                    }
+                    Some(Reference::Time) | None => {}
+                }
+            }
+        }
+
+        self.behavioral = behavioral;
+        self.perturbed = perturbed;
+
+        match undefined {
+            Some(name) => Err(SolveError::Undefined { name }),
+            None => Ok(()),
+        }
+    }
+
+    /// The value of `behavioral` with the present solution, if it has one.
+    fn behavioral_value(&self, behavioral: &Behavioral) -> Option<f64> {
+        let values: Vec<_> = behavioral
+            .references
+            .iter()
+            .map(|reference| {
+                reference
+                    .as_ref()
+                    .and_then(|reference| {
+                        self.reference_value(&self.equations, reference, behavioral.t)
+                    })
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        behavioral.source.value(&values)
+    }
+
+    /// The value of `reference` at the time `t`, with the unknowns taken from `le`.
+    fn reference_value(&self, le: &LinearEquations, reference: &Reference, t: f64) -> Option<c64> {
+        match reference {
+            Reference::Time => Some(c64::real(t)),
+            Reference::Net(net) => Some(le.x[*net as usize]),
+            Reference::Parameter {
+                type_id,
+                idx,
+                parameter,
+            } => self.component_parameter(le, *type_id, *idx, parameter),
+        }
+    }
+
+    /// Clears the equations and stamps the [`Newton::gmin`] shunts and every component,
+    /// the components last and by [`Component::PRIORITY`]. Behavioral sources are
+    /// linearized around the present solution first, except in small-signal analysis,
+    /// which keeps them linearized around the operating point.
+    pub fn stamp_all(&mut self, mode: Mode) -> Result<(), SolveError> {
+        if !self.resolved {
+            self.resolve_behavioral();
+        }
+
+        if !matches!(mode, Mode::Ac { .. }) {
+            self.linearize_behavioral(mode)?;
+        }
+
+        self.equations.clear();
+
+        if self.newton.gmin > 0. {
+            let nodes: Vec<_> = (0..self.unknowns)
+                .filter(|&idx| !self.is_branch(idx as usize))
+                .collect();
+            self.equations
+                .stamp_shunt(nodes, c64::real(self.newton.gmin));
+        }
+
+        for behavioral in &self.behavioral {
+            behavioral.source.stamp(
+                &mut self.equations,
+                mode,
+                behavioral.nets,
+                behavioral.branch,
+                &behavioral.linearization,
+            );
+        }
+
+        for type_id in &self.order {
+            let component = &self.circuit[type_id];
             (component.stamp_all_fn)(
                 &component.buffer,
                 &mut self.equations,
-                dt,
+                mode,
                 &component.terminals[..],
             );
         }
 
-        for (_, component) in &mut self.circuit {
+        Ok(())
+    }
+
+    /// Lets every component update its state from the solution. Behavioral sources
+    /// stay linearized around the last iteration, which small-signal analysis reuses.
+    pub fn post_stamp_all(&mut self, mode: Mode) {
+        for component in self.circuit.values_mut() {
             (component.post_stamp_all_fn)(
                 &mut component.buffer,
                 &self.equations,
-                dt,
+                mode,
+                &component.terminals[..],
+                &self.tables,
+            );
+        }
+
+        if let Mode::Transient { dt, .. } = mode {
+            for behavioral in &mut self.behavioral {
+                behavioral.t += dt;
+            }
+        }
+    }
+
+    /// The largest [`Component::truncation_error`] of the step just solved, if any component has one.
+    pub fn truncation_error(&self, mode: Mode, tolerance: &Tolerance) -> Option<f64> {
+        self.circuit
+            .values()
+            .filter_map(|component| {
+                (component.truncation_error_fn)(
+                    &component.buffer,
+                    &self.equations,
+                    mode,
+                    &component.terminals[..],
+                    tolerance,
+                )
+            })
+            .reduce(f64::max)
+    }
+
+    /// The earliest [`Component::breakpoint`] within the step just solved, if any.
+    pub fn breakpoint(&self, mode: Mode) -> Option<f64> {
+        self.circuit
+            .values()
+            .filter_map(|component| {
+                (component.breakpoint_fn)(
+                    &component.buffer,
+                    &self.equations,
+                    mode,
+                    &component.terminals[..],
+                )
+            })
+            .reduce(f64::min)
+    }
+
+    /// Lets every nonlinear component take its operating point from the solution,
+    /// returning whether any of them is not settled yet, see [`Component::limit`].
+    fn limit_all(&mut self, mode: Mode) -> bool {
+        let tolerance = self.newton.tolerance;
+        let mut limited = false;
+
+        for component in self.circuit.values_mut().filter(|c| c.is_nonlinear(mode)) {
+            limited |= (component.limit_all_fn)(
+                &mut component.buffer,
+                &self.equations,
+                mode,
                 &component.terminals[..],
+                &tolerance,
+            );
+        }
+
+        limited
+    }
+
+    pub(crate) fn is_nonlinear(&self, mode: Mode) -> bool {
+        self.circuit.values().any(|c| c.is_nonlinear(mode)) || !self.behavioral.is_empty()
+    }
+
+    /// Whether no unknown moved further from `previous` than the tolerance allows.
+    fn has_converged(&self, previous: &[c64]) -> bool {
+        let tolerance = &self.newton.tolerance;
+
+        self.equations
+            .x
+            .iter()
+            .zip(previous)
+            .enumerate()
+            .all(|(idx, (&x, &x_previous))| {
+                let magnitude = x.norm().max(x_previous.norm());
+                let allowed = if self.is_branch(idx) {
+                    tolerance.current(magnitude)
+                } else {
+                    tolerance.voltage(magnitude)
+                };
+
+                (x - x_previous).norm() <= allowed
+            })
+    }
+
+    /// Stamps and solves the circuit. With nonlinear components this iterates
+    /// Newton–Raphson, restamping around every solution until it settles.
+    /// Small-signal analysis only linearizes around the operating point the
+    /// components already hold, so it is solved once.
+    pub fn solve(&mut self, mode: Mode) -> Result<(), SolveError> {
+        self.stamp_all(mode)?;
+        self.equations.solve_direct()?;
+
+        if matches!(mode, Mode::Ac { .. }) || !self.is_nonlinear(mode) {
+            return Ok(());
+        }
+
+        let mut converged = false;
+
+        for _ in 0..self.newton.max_iterations {
+            let unsettled = self.limit_all(mode);
+            if converged && !unsettled {
+                return Ok(());
+            }
+
+            let previous = self.equations.x.clone();
+
+            self.stamp_all(mode)?;
+            self.equations.solve_direct()?;
+
+            converged = self.has_converged(&previous);
+        }
+
+        Err(SolveError::NonConvergence {
+            iterations: self.newton.max_iterations,
+        })
+    }
+
+    /// Advances the circuit by `dt` with backward Euler, see [`Circuit::step_with`].
+    pub fn step(&mut self, dt: f64) -> Result<(), SolveError> {
+        self.step_with(dt, Integration::default())
+    }
+
+    /// Advances the circuit by `dt`: solves and updates component state.
+    pub fn step_with(&mut self, dt: f64, integration: Integration) -> Result<(), SolveError> {
+        let mode = Mode::Transient { dt, integration };
+
+        self.solve(mode)?;
+        self.post_stamp_all(mode);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use crate::component::{
+        Capacitor, CurrentControlledCurrentSource, CurrentControlledVoltageSource, DC1Source,
+        DC2Source, DCCurrentSource, Ground, Resistor, VoltageControlledCurrentSource,
+        VoltageControlledVoltageSource,
+    };
+
+    use super::*;
+
+    /// Conducts `i = k v |v|`, a stand-in for the semiconductor devices.
+    #[derive(Pod, Zeroable, Clone, Copy)]
+    #[repr(C)]
+    struct SquareLaw {
+        k: f64,
+    }
+
+    impl Component for SquareLaw {
+        type State = f64;
+        const TERMINAL_COUNT: usize = 2;
+        const PRIORITY: usize = 10;
+        const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (0, 1), (1, 0), (1, 1)];
+        const NONLINEAR: bool = true;
+
+        fn stamp(&self, le: &mut LinearEquations, _: Mode, [n1, n2]: [u32; 2], &v0: &f64) {
+            let g = c64::real(2. * self.k * v0.abs());
+            let i_eq = c64::real(self.k * v0 * v0.abs()) - g * c64::real(v0);
+
+            le.add_a(n1, n1, g);
+            le.add_a(n1, n2, -g);
+            le.add_a(n2, n1, -g);
+            le.add_a(n2, n2, g);
+            le.add_b(n1, -i_eq);
+            le.add_b(n2, i_eq);
+        }
+
+        fn limit(
+            &self,
+            le: &LinearEquations,
+            _: Mode,
+            [n1, n2]: [u32; 2],
+            v0: &mut f64,
+            _: &Tolerance,
+        ) -> bool {
+            *v0 = le.get_voltage_across(n1, n2).re;
+            false
+        }
+    }
+
+    fn assert_close(z: c64, expected: f64) {
+        assert!((z.re - expected).abs() < 1e-9, "{} != {}", z.re, expected);
+        assert!(z.im.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_source_branch_current() {
+        let mut circuit = Circuit::new();
+        let (a, b) = (circuit.net("a"), circuit.net("b"));
+
+        circuit.put(DC1Source { voltage_volt: 10. }, Some("V1".into()), [a]);
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            Some("R1".into()),
+            [a, b],
+        );
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            Some("R2".into()),
+            [b, a],
+        );
+        circuit.put(Ground, None, [b]);
+
+        circuit.step(1.).unwrap();
+
+        assert_close(circuit.voltage("a").unwrap(), 10.);
+        assert_close(circuit.voltage("b").unwrap(), 0.);
+        assert_close(circuit.parameter("V1", "I").unwrap(), 20e-3);
+        assert_close(circuit.parameter("V1", "P").unwrap(), 0.2);
+        assert_close(circuit.parameter("R1", "I").unwrap(), 10e-3);
+    }
+
+    #[test]
+    fn test_sources_share_a_node() {
+        let mut circuit = Circuit::new();
+        let a = circuit.net("a");
+
+        circuit.put(DC1Source { voltage_volt: 5. }, Some("V1".into()), [a]);
+        circuit.put(DC1Source { voltage_volt: 5. }, Some("V2".into()), [a]);
+
+        // two ideal sources in parallel leave their current split undetermined
+        assert!(matches!(
+            circuit.solve(Mode::DC_FULL),
+            Err(SolveError::Singular(_))
+        ));
+    }
+
+    #[test]
+    fn test_grounds() {
+        let mut circuit = Circuit::new();
+        let [a, b, c] = ["a", "b", "c"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 1. }, Some("V1".into()), [a]);
+        for (from, to) in [(a, b), (a, c)] {
+            circuit.put(
+                Resistor {
+                    resistance_ohm: 1e3,
+                },
+                None,
+                [from, to],
+            );
+        }
+
+        // twice on one net, and on two nets that are one node through the reference
+        circuit.put(Ground, None, [b]);
+        circuit.put(Ground, None, [b]);
+        circuit.put(Ground, None, [c]);
+
+        circuit.step(1.).unwrap();
+
+        assert_close(circuit.voltage("b").unwrap(), 0.);
+        assert_close(circuit.voltage("c").unwrap(), 0.);
+        assert_close(circuit.parameter("V1", "I").unwrap(), 2e-3);
+    }
+
+    #[test]
+    fn test_gmin_shunts_unnamed_nodes() {
+        let mut circuit = Circuit::new();
+        let [a, gnd] = ["a", "gnd"].map(|net| circuit.net(net));
+
+        // a node between two capacitors that was never given a name
+        circuit.put(DC1Source { voltage_volt: 1. }, None, [a]);
+        let mid = circuit.unknowns;
+        for nets in [[a, mid], [mid, gnd]] {
+            circuit.put(
+                Capacitor {
+                    capacitance_f: 1e-6,
+                },
+                None,
+                nets,
             );
         }
+        circuit.put(Ground, None, [gnd]);
+
+        assert!(circuit.solve(Mode::DC_FULL).is_err());
+
+        circuit.newton.gmin = 1e-12;
+        circuit.solve(Mode::DC_FULL).unwrap();
+        assert_close(circuit.equations.x[mid as usize], 0.);
+    }
+
+    #[test]
+    fn test_floating_source_in_series() {
+        let mut circuit = Circuit::new();
+        let (a, b, c) = (circuit.net("a"), circuit.net("b"), circuit.net("c"));
+
+        circuit.put(DC1Source { voltage_volt: 2. }, Some("BIAS".into()), [a]);
+        circuit.put(DC2Source { voltage_volt: 3. }, Some("SIG".into()), [b, a]);
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            Some("R1".into()),
+            [b, c],
+        );
+        circuit.put(Ground, None, [c]);
+
+        circuit.step(1.).unwrap();
+
+        assert_close(circuit.voltage("b").unwrap(), 5.);
+        assert_close(circuit.parameter("SIG", "I").unwrap(), 5e-3);
+        assert_close(circuit.parameter("BIAS", "I").unwrap(), 5e-3);
+        assert_close(circuit.parameter("SIG", "P").unwrap(), 15e-3);
+    }
+
+    #[test]
+    fn test_current_source_into_resistor() {
+        let mut circuit = Circuit::new();
+        let (a, b) = (circuit.net("a"), circuit.net("b"));
+
+        circuit.put(
+            DCCurrentSource {
+                current_ampere: 2e-3,
+            },
+            Some("I1".into()),
+            [a, b],
+        );
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            None,
+            [a, b],
+        );
+        circuit.put(Ground, None, [b]);
+
+        circuit.step(1.).unwrap();
+
+        assert_close(circuit.voltage("a").unwrap(), 2.);
+        assert_close(circuit.parameter("I1", "V").unwrap(), 2.);
+        assert_close(circuit.parameter("I1", "P").unwrap(), 4e-3);
     }
 
-    pub fn solve(&mut self) {
-        self.equations.solve();
+    #[test]
+    fn test_controlled_sources() {
+        let mut circuit = Circuit::new();
+        let [i, e, g, h, f, gnd] = ["in", "e", "g", "h", "f", "gnd"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 1. }, Some("V1".into()), [i]);
+        circuit.put(Ground, None, [gnd]);
+
+        // V1 drives 1mA out of its positive terminal, so -1mA flows through it
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            None,
+            [i, gnd],
+        );
+        let kc = circuit.branch_current_of("V1").unwrap();
+        let h1 = CurrentControlledVoltageSource {
+            transresistance_ohm: 2e3,
+        };
+        circuit.put(h1, Some("H1".into()), [h, gnd, kc]);
+        let f1 = CurrentControlledCurrentSource { gain: 5. };
+        circuit.put(f1, Some("F1".into()), [f, gnd, kc]);
+
+        let e1 = VoltageControlledVoltageSource { gain: 10. };
+        circuit.put(e1, Some("E1".into()), [e, gnd, i, gnd]);
+        let g1 = VoltageControlledCurrentSource {
+            transconductance_s: 1e-3,
+        };
+        circuit.put(g1, Some("G1".into()), [g, gnd, i, gnd]);
+
+        for load in [e, g, h, f] {
+            circuit.put(
+                Resistor {
+                    resistance_ohm: 1e3,
+                },
+                None,
+                [load, gnd],
+            );
+        }
+
+        assert_eq!(circuit.branch_current_of("E1"), Some(circuit.unknowns - 1));
+        assert_eq!(circuit.branch_current_of("G1"), None);
+        assert_eq!(circuit.branch_current_of("nothing"), None);
+
+        circuit.step(1.).unwrap();
+
+        assert_close(circuit.voltage("e").unwrap(), 10.);
+        assert_close(circuit.parameter("E1", "I").unwrap(), 10e-3);
+        assert_close(circuit.voltage("g").unwrap(), 1.);
+        assert_close(circuit.parameter("H1", "Ic").unwrap(), -1e-3);
+        assert_close(circuit.voltage("h").unwrap(), -2.);
+        assert_close(circuit.parameter("F1", "I").unwrap(), -5e-3);
+        assert_close(circuit.voltage("f").unwrap(), -5.);
+    }
+
+    #[test]
+    fn test_newton_raphson() {
+        let mut circuit = Circuit::new();
+        let [a, b, gnd] = ["a", "b", "gnd"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 1. }, None, [a]);
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            None,
+            [a, b],
+        );
+        circuit.put(SquareLaw { k: 1e-3 }, None, [b, gnd]);
+        circuit.put(Ground, None, [gnd]);
+
+        circuit.newton.max_iterations = 1;
+        assert!(matches!(
+            circuit.solve(Mode::DC_FULL),
+            Err(SolveError::NonConvergence { iterations: 1 })
+        ));
+
+        circuit.newton.max_iterations = 100;
+        circuit.solve(Mode::DC_FULL).unwrap();
+
+        // (1 - v) / 1k = 1m v², so v² + v - 1 = 0
+        let v = circuit.voltage("b").unwrap();
+        assert!((v.re - (5f64.sqrt() - 1.) / 2.).abs() < 1e-5, "{}", v);
     }
 }
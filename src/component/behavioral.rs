@@ -0,0 +1,409 @@
+use crate::{
+    component::Mode,
+    expression::Expression,
+    numerical::{LinearEquations, c64},
+};
+
+/// What a [`BehavioralSource`] sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantity {
+    /// The voltage from its positive to its negative terminal, as its `V`.
+    Voltage,
+    /// The current it drives out of its positive terminal, as its `I`.
+    Current,
+}
+
+impl Quantity {
+    /// The netlist parameter holding the expression.
+    pub fn parameter(&self) -> &'static str {
+        match self {
+            Quantity::Voltage => "V",
+            Quantity::Current => "I",
+        }
+    }
+}
+
+/// SPICE's B source: a voltage or current given by an [`Expression`] of node voltages
+/// like `V_out`, parameters of other components like `I_R1` and the time `t`.
+///
+/// It isn't a [`crate::Component`], since an expression doesn't fit a `Pod` and it reads
+/// the rest of the circuit. The circuit keeps it apart, resolves its variables once it is
+/// built and linearizes it around every Newton–Raphson solution.
+#[derive(Debug, Clone)]
+pub struct BehavioralSource {
+    pub quantity: Quantity,
+    pub expression: Expression,
+    /// The variables of the expression by name and subscript.
+    variables: Vec<(String, Option<String>)>,
+    /// The derivative by each of the variables, where it has a symbolic one.
+    derivatives: Vec<Option<Expression>>,
+}
+
+/// A [`BehavioralSource`] around the solution it was linearized at: its value there,
+/// and how much it changes with each unknown.
+#[derive(Debug, Clone, Default)]
+pub struct Linearization {
+    pub value: f64,
+    pub jacobian: Vec<(u32, f64)>,
+}
+
+impl Linearization {
+    /// Flat in each of `unknowns`, ready to be linearized around a solution.
+    pub fn depending_on(unknowns: impl IntoIterator<Item = u32>) -> Self {
+        let mut linearization = Self::default();
+        for idx in unknowns {
+            linearization.add(idx, 0.);
+        }
+
+        linearization
+    }
+
+    /// Makes it flat again, keeping the unknowns it depends on.
+    pub fn reset(&mut self) {
+        for (_, slope) in &mut self.jacobian {
+            *slope = 0.;
+        }
+    }
+
+    /// Adds `slope` to the change with the unknown `idx`.
+    pub fn add(&mut self, idx: u32, slope: f64) {
+        match self.jacobian.iter_mut().find(|(i, _)| *i == idx) {
+            Some((_, total)) => *total += slope,
+            None => self.jacobian.push((idx, slope)),
+        }
+    }
+
+    /// The value with the unknowns at `x`, along the tangent.
+    fn tangent_offset(&self, x: &[c64]) -> f64 {
+        let at = self
+            .jacobian
+            .iter()
+            .map(|&(j, slope)| slope * x[j as usize].re)
+            .sum::<f64>();
+
+        self.value - at
+    }
+}
+
+/// The real part of `z` if it is finite and `z` is real, up to the rounding left
+/// by phasors like in `(1<45) * (1<-45)`.
+fn real(z: c64) -> Option<f64> {
+    (z.re.is_finite() && z.im.abs() <= 1e-9 * z.norm()).then_some(z.re)
+}
+
+impl BehavioralSource {
+    pub fn new(quantity: Quantity, expression: Expression) -> Self {
+        let variables: Vec<_> = expression
+            .variables()
+            .into_iter()
+            .map(|(name, subscript)| (name.to_string(), subscript.map(str::to_string)))
+            .collect();
+        let derivatives = variables
+            .iter()
+            .map(|(name, subscript)| expression.derivative(name, subscript.as_deref()))
+            .collect();
+
+        Self {
+            quantity,
+            expression,
+            variables,
+            derivatives,
+        }
+    }
+
+    /// The variables of the expression by name and subscript.
+    pub fn variables(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.variables
+            .iter()
+            .map(|(name, subscript)| (name.as_str(), subscript.as_deref()))
+    }
+
+    /// The value with the variables at `values`, in the order of
+    /// [`BehavioralSource::variables`], and its derivative by each of them into `slopes`.
+    /// Those without a symbolic derivative are differentiated numerically. `None` where
+    /// the value or a derivative isn't real and finite.
+    pub fn evaluate(&self, values: &[c64], slopes: &mut [f64]) -> Option<f64> {
+        let value = self.nudged_value(values, None)?;
+
+        for (idx, (slope, derivative)) in slopes.iter_mut().zip(&self.derivatives).enumerate() {
+            *slope = match derivative {
+                Some(derivative) => derivative
+                    .evaluate(&|name, subscript| self.lookup(values, None, name, subscript))
+                    .and_then(real)?,
+                None => {
+                    let h = 1e-6 * (1. + values[idx].re.abs());
+                    (self.nudged_value(values, Some((idx, h)))? - value) / h
+                }
+            };
+        }
+
+        Some(value)
+    }
+
+    /// The value of the variable `name` with `subscript` among `values`, the one at the
+    /// position of `nudge` moved by its step.
+    fn lookup(
+        &self,
+        values: &[c64],
+        nudge: Option<(usize, f64)>,
+        name: &str,
+        subscript: Option<&str>,
+    ) -> Option<c64> {
+        let idx = self
+            .variables()
+            .position(|variable| variable == (name, subscript))?;
+
+        match nudge {
+            Some((nudged, h)) if nudged == idx => Some(values[idx] + c64::real(h)),
+            _ => values.get(idx).copied(),
+        }
+    }
+
+    /// The value with the variables at `values`, like [`BehavioralSource::evaluate`].
+    pub fn value(&self, values: &[c64]) -> Option<f64> {
+        self.nudged_value(values, None)
+    }
+
+    fn nudged_value(&self, values: &[c64], nudge: Option<(usize, f64)>) -> Option<f64> {
+        self.expression
+            .evaluate(&|name, subscript| self.lookup(values, nudge, name, subscript))
+            .and_then(real)
+    }
+
+    /// Stamps it linearized as `linearization` between `p` and `n`, a voltage with its current
+    /// as the unknown `branch`. Small-signal analysis only sees the Jacobian.
+    pub fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        [p, n]: [u32; 2],
+        branch: Option<u32>,
+        linearization: &Linearization,
+    ) {
+        let offset = match mode {
+            Mode::Ac { .. } => 0.,
+            _ => linearization.tangent_offset(&net.x),
+        };
+
+        match self.quantity {
+            // v_p - v_n - J x = f - J x0
+            Quantity::Voltage => {
+                let k = branch.expect("a voltage has a branch unknown");
+                net.stamp_voltage_source(p, Some(n), k, c64::real(offset));
+                for &(j, slope) in &linearization.jacobian {
+                    net.add_a(k, j, c64::real(-slope));
+                }
+            }
+            // the current out of p is f + J (x - x0)
+            Quantity::Current => {
+                net.add_b(p, c64::real(offset));
+                net.add_b(n, c64::real(-offset));
+                for &(j, slope) in &linearization.jacobian {
+                    net.add_a(p, j, c64::real(-slope));
+                    net.add_a(n, j, c64::real(slope));
+                }
+            }
+        }
+    }
+
+    /// The matrix entries stamping it linearized in the unknowns `dependencies` takes.
+    pub fn coordinates(
+        &self,
+        [p, n]: [u32; 2],
+        branch: Option<u32>,
+        dependencies: impl IntoIterator<Item = u32>,
+    ) -> Vec<(u32, u32)> {
+        let (own, rows) = match (self.quantity, branch) {
+            (Quantity::Voltage, Some(k)) => (
+                vec![(p, k), (k, p), (n, k), (k, n), (p, p), (n, n)],
+                vec![k],
+            ),
+            _ => (vec![(p, p), (n, n)], vec![p, n]),
+        };
+
+        let dependent = dependencies
+            .into_iter()
+            .flat_map(|j| rows.iter().map(move |&row| (row, j)));
+
+        own.into_iter().chain(dependent).collect()
+    }
+
+    /// Its `V`, `I` and `P`, with `value` what it sets, `across` the voltage across it
+    /// and `branch` the current of its branch unknown, if it is a voltage.
+    pub fn parameter(
+        &self,
+        value: f64,
+        across: c64,
+        branch: Option<c64>,
+        parameter: &str,
+    ) -> Option<c64> {
+        let (v, i) = match (self.quantity, branch) {
+            (Quantity::Voltage, Some(i)) => (c64::real(value), -i),
+            _ => (across, c64::real(value)),
+        };
+
+        match parameter {
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AcSweep, AnalysisError, BuildError, CircuitBuilder, ComponentLibrary, DcOperatingPoint,
+        Parser, Probe, Sweep, Timestep, Transient, component::Integration, parser::build,
+    };
+
+    #[test]
+    fn test_behavioral_sources() {
+        // draws V_a^2 mA out of a, so that 1 - V_a = V_a^2
+        let mut circuit = build(
+            r#"
+            dc-source-1-terminal                    in              V=1
+            resistor                        "R1"    in a            R=1k
+            behavioral-current-source       "B1"    gnd a           I=0.001*V_a^2
+            behavioral-source               "B2"    out gnd         V=2000*I_R1
+            ground                                  gnd
+            "#,
+        );
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        let v_a = (5f64.sqrt() - 1.) / 2.;
+
+        let a = op.voltage("a").unwrap().re;
+        assert!((a - v_a).abs() < 1e-9, "{a}");
+        assert!((op.parameter("B1", "I").unwrap().re - v_a * v_a * 1e-3).abs() < 1e-12);
+        // I_R1 is (1 - V_a) / 1k
+        let out = op.voltage("out").unwrap().re;
+        assert!((out - 2. * (1. - v_a)).abs() < 1e-9, "{out}");
+        assert!((op.parameter("B2", "V").unwrap().re - out).abs() < 1e-12);
+
+        let netlist = r#"
+            behavioral-source               "B1"    out gnd         V=V_nowhere
+            ground                                  gnd
+        "#;
+        let mut builder = CircuitBuilder::new();
+        builder.add_commands(Parser::from(netlist).parse_commands().unwrap());
+        let Err(errors) = builder.build(&ComponentLibrary::with_builtins()) else {
+            panic!("V_nowhere is not a net");
+        };
+        assert!(matches!(
+            &errors[..],
+            [BuildError::UnresolvedVariable { variable, .. }] if variable == "V_nowhere"
+        ));
+    }
+
+    #[test]
+    fn test_behavioral_build_errors() {
+        let netlist = r#"
+            dc-source-1-terminal            "V1"    in              V=1
+            resistor                        "V1"    in gnd          R=1k
+            behavioral-source               "B1"    a gnd           V=2*V_in
+            behavioral-current-source       "B2"    a gnd           I=I_B1
+            behavioral-source               "B3"    b gnd           V=foo(V_in)
+            behavioral-source               "B4"    c gnd           V=2*exp(V_in,2)
+            ground                                  gnd
+        "#;
+        let mut builder = CircuitBuilder::new();
+        builder.add_commands(Parser::from(netlist).parse_commands().unwrap());
+        let Err(errors) = builder.build(&ComponentLibrary::with_builtins()) else {
+            panic!("V1 is taken, B1 is behavioral and foo isn't a function");
+        };
+
+        assert!(matches!(
+            &errors[..],
+            [
+                BuildError::DuplicateName { name },
+                BuildError::BehavioralReference { name: Some(source), variable },
+                BuildError::UnknownFunction { function: foo, arguments: 1, .. },
+                BuildError::UnknownFunction { function: exp, arguments: 2, .. },
+            ] if name == "V1" && source == "B2" && variable == "I_B1" && foo == "foo" && exp == "exp"
+        ));
+    }
+
+    #[test]
+    fn test_behavioral_undefined_values() {
+        for (expression, v_in) in [("sqrt(V_in)", -1.), ("1/V_in", 0.)] {
+            let mut circuit = build(&format!(
+                r#"
+                dc-source-1-terminal                    in              V={v_in}
+                behavioral-source               "B1"    out gnd         V={expression}
+                ground                                  gnd
+                "#
+            ));
+
+            assert_eq!(
+                DcOperatingPoint.run(&mut circuit).unwrap_err(),
+                AnalysisError::Undefined {
+                    point: 0.,
+                    name: Some("B1".into())
+                },
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_behavioral_source_over_time() {
+        let mut circuit = build(
+            r#"
+            behavioral-source               "B1"    in gnd          V=sin(6283.185307179586*t)
+            resistor                                in gnd          R=1k
+            ground                                  gnd
+            "#,
+        );
+
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: 1e-3,
+            step_s: 10e-6,
+            timestep: Timestep::Fixed,
+            integration: Integration::BackwardEuler,
+            probes: vec![Probe::voltage("in")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+        let v = waveform.column("V(in)").unwrap();
+        for (&t, v) in waveform.time.iter().zip(v) {
+            let expected = (2. * std::f64::consts::PI * 1e3 * t).sin();
+            assert!((v.re - expected).abs() < 1e-6, "{} at {t}", v.re);
+        }
+    }
+
+    #[test]
+    fn test_behavioral_small_signal() {
+        // squares the input around 1.5V, a small-signal gain of 3
+        let netlist = r#"
+            dc-source-1-terminal                    bias            V=1.5
+            ac-source-2-terminal                    in bias         V=1 f=1k
+            behavioral-source               "B1"    out gnd         V=V_in^2
+            ground                                  gnd
+        "#;
+
+        let mut circuit = build(netlist);
+        DcOperatingPoint.run(&mut circuit).unwrap();
+        assert!((circuit.voltage("out").unwrap().re - 2.25).abs() < 1e-9);
+
+        // the sweep finds the operating point by itself, rather than linearizing around 0V
+        let mut circuit = build(netlist);
+        let response = AcSweep {
+            sweep: Sweep::Linear { points: 1 },
+            start_hz: 1e3,
+            stop_hz: 1e3,
+            probes: vec![Probe::voltage("out")],
+        }
+        .run(&mut circuit)
+        .unwrap();
+
+        let out = response.column("V(out)").unwrap()[0];
+        assert!(
+            (out.re - 3.).abs() < 1e-9 && out.im.abs() < 1e-9,
+            "{}",
+            out.re
+        );
+    }
+}
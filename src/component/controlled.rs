@@ -0,0 +1,236 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    component::{Component, FromParameters, Mode, Parameters},
+    numerical::{LinearEquations, c64},
+};
+
+// Voltage controlled sources have the terminal layout [out+, out-, in+, in-] and
+// sense v(in+) - v(in-) without drawing current. Current controlled ones have
+// [out+, out-] and then the branch unknown of the element whose current they
+// sense, see `Component::CURRENT_BRANCH`, like SPICE's H and F with a Vname.
+
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct VoltageControlledVoltageSource {
+    pub gain: f64,
+}
+
+impl FromParameters for VoltageControlledVoltageSource {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            gain: parameters.real("K"),
+        }
+    }
+}
+
+impl Component for VoltageControlledVoltageSource {
+    type State = ();
+    const TERMINAL_COUNT: usize = 5;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(4);
+    const PRIORITY: usize = 20;
+    const PARAMETERS: &[&'static str] = &["K", "V", "I", "P"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 4), (1, 4), (4, 0), (4, 1), (4, 2), (4, 3)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        _: Mode,
+        [op, on, ip, in_, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+    ) {
+        let gain = c64::new(self.gain, 0.);
+
+        net.stamp_voltage_source(op, Some(on), k, c64::ZERO);
+        net.add_a(k, ip, -gain);
+        net.add_a(k, in_, gain);
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [op, on, _, _, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = net.get_voltage_across(op, on);
+        let i = -net.get_branch_current(k);
+
+        match parameter {
+            "K" => Some(c64::new(self.gain, 0.)),
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct VoltageControlledCurrentSource {
+    pub transconductance_s: f64,
+}
+
+impl FromParameters for VoltageControlledCurrentSource {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            transconductance_s: parameters.real("G"),
+        }
+    }
+}
+
+impl Component for VoltageControlledCurrentSource {
+    type State = ();
+    const TERMINAL_COUNT: usize = 4;
+    const PRIORITY: usize = 20;
+    const PARAMETERS: &[&'static str] = &["G", "V", "I", "P"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 2), (0, 3), (1, 2), (1, 3)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        _: Mode,
+        [op, on, ip, in_]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+    ) {
+        let gm = c64::new(self.transconductance_s, 0.);
+
+        net.add_a(op, ip, -gm);
+        net.add_a(op, in_, gm);
+        net.add_a(on, ip, gm);
+        net.add_a(on, in_, -gm);
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [op, on, ip, in_]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let gm = c64::new(self.transconductance_s, 0.);
+        let v = net.get_voltage_across(op, on);
+        let i = gm * net.get_voltage_across(ip, in_);
+
+        match parameter {
+            "G" => Some(gm),
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct CurrentControlledVoltageSource {
+    pub transresistance_ohm: f64,
+}
+
+impl FromParameters for CurrentControlledVoltageSource {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            transresistance_ohm: parameters.real("R"),
+        }
+    }
+}
+
+impl Component for CurrentControlledVoltageSource {
+    type State = ();
+    const TERMINAL_COUNT: usize = 4;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(3);
+    const PRIORITY: usize = 20;
+    const PARAMETERS: &[&'static str] = &["R", "V", "I", "P", "Ic"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 3), (1, 3), (3, 0), (3, 1), (3, 2)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        _: Mode,
+        [op, on, kc, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+    ) {
+        net.stamp_voltage_source(op, Some(on), k, c64::ZERO);
+        net.add_a(k, kc, -c64::new(self.transresistance_ohm, 0.));
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [op, on, kc, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = net.get_voltage_across(op, on);
+        let i = -net.get_branch_current(k);
+
+        match parameter {
+            "R" => Some(c64::new(self.transresistance_ohm, 0.)),
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            "Ic" => Some(net.get_branch_current(kc)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct CurrentControlledCurrentSource {
+    pub gain: f64,
+}
+
+impl FromParameters for CurrentControlledCurrentSource {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            gain: parameters.real("K"),
+        }
+    }
+}
+
+impl Component for CurrentControlledCurrentSource {
+    type State = ();
+    const TERMINAL_COUNT: usize = 3;
+    const PRIORITY: usize = 20;
+    const PARAMETERS: &[&'static str] = &["K", "V", "I", "P", "Ic"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 2), (1, 2)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        _: Mode,
+        [op, on, kc]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+    ) {
+        let gain = c64::new(self.gain, 0.);
+
+        net.add_a(op, kc, -gain);
+        net.add_a(on, kc, gain);
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [op, on, kc]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = net.get_voltage_across(op, on);
+        let ic = net.get_branch_current(kc);
+        let i = c64::new(self.gain, 0.) * ic;
+
+        match parameter {
+            "K" => Some(c64::new(self.gain, 0.)),
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            "Ic" => Some(ic),
+            _ => None,
+        }
+    }
+}
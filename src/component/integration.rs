@@ -0,0 +1,180 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::numerical::c64;
+
+/// How reactive components discretize their derivative over a transient step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integration {
+    /// First order and L-stable, damps resonances noticeably.
+    #[default]
+    BackwardEuler,
+    /// Second order and energy-preserving, but may ring on sharp edges.
+    Trapezoidal,
+    /// Second order backward differentiation (Gear), damps only slightly.
+    Gear2,
+}
+
+impl Integration {
+    /// Order of accuracy, the local truncation error grows with `dt^(order + 1)`.
+    pub fn order(self) -> i32 {
+        match self {
+            Integration::BackwardEuler => 1,
+            Integration::Trapezoidal | Integration::Gear2 => 2,
+        }
+    }
+
+    /// The local truncation error is `error_constant * dt^(order + 1)` times
+    /// the `order + 1`th derivative of the integrated quantity.
+    pub fn error_constant(self) -> f64 {
+        match self {
+            Integration::BackwardEuler => 1. / 2.,
+            Integration::Trapezoidal => 1. / 12.,
+            Integration::Gear2 => 2. / 9.,
+        }
+    }
+
+    /// Discretizes `dy/dt` at the end of a step of `dt` as `a0 * y + history`, returning `(a0, history)`.
+    ///
+    /// `y_old` and `dy_old` are the value and derivative at the start of the step, `y_older`
+    /// is the value one step of `dt_old` before that. A zero `dt_old` means there is no such
+    /// point yet, and Gear falls back to backward Euler.
+    pub fn derivative(
+        self,
+        dt: f64,
+        dt_old: f64,
+        y_old: c64,
+        y_older: c64,
+        dy_old: c64,
+    ) -> (f64, c64) {
+        match self {
+            Integration::BackwardEuler => (1. / dt, -y_old / c64::real(dt)),
+            Integration::Gear2 if dt_old == 0. => {
+                Integration::BackwardEuler.derivative(dt, dt_old, y_old, y_older, dy_old)
+            }
+            Integration::Trapezoidal => (2. / dt, -y_old * c64::real(2. / dt) - dy_old),
+            Integration::Gear2 => {
+                // variable-step coefficients, `3/2dt`, `-2/dt` and `1/2dt` for equal steps
+                let rho = dt / dt_old;
+                let a0 = (1. + 2. * rho) / (dt * (1. + rho));
+                let a1 = -(1. + rho) / dt;
+                let a2 = rho * rho / (dt * (1. + rho));
+                (a0, y_old * c64::real(a1) + y_older * c64::real(a2))
+            }
+        }
+    }
+}
+
+/// A state variable and its derivative at the last accepted time point,
+/// plus the earlier points the multistep methods and error estimates need.
+#[derive(Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub(crate) struct History {
+    y_old_re: f64,
+    y_old_im: f64,
+    y_older_re: f64,
+    y_older_im: f64,
+    y_oldest_re: f64,
+    y_oldest_im: f64,
+    dy_old_re: f64,
+    dy_old_im: f64,
+    dt_old: f64,
+    dt_older: f64,
+}
+
+impl History {
+    /// At rest at `y`, as found by the operating point.
+    pub(crate) fn settled(y: c64) -> Self {
+        Self {
+            y_old_re: y.re,
+            y_old_im: y.im,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn y_old(&self) -> c64 {
+        c64::new(self.y_old_re, self.y_old_im)
+    }
+
+    pub(crate) fn y_older(&self) -> c64 {
+        c64::new(self.y_older_re, self.y_older_im)
+    }
+
+    pub(crate) fn y_oldest(&self) -> c64 {
+        c64::new(self.y_oldest_re, self.y_oldest_im)
+    }
+
+    pub(crate) fn dy_old(&self) -> c64 {
+        c64::new(self.dy_old_re, self.dy_old_im)
+    }
+
+    /// The derivative at the end of a step of `dt` as `a0 * y + history`, see [`Integration::derivative`].
+    pub(crate) fn derivative(&self, integration: Integration, dt: f64) -> (f64, c64) {
+        integration.derivative(dt, self.dt_old, self.y_old(), self.y_older(), self.dy_old())
+    }
+
+    /// Estimates the local truncation error of a step of `dt` ending at `y`
+    /// from the divided differences over the last points.
+    pub(crate) fn truncation_error(&self, integration: Integration, dt: f64, y: c64) -> f64 {
+        let [h0, h1, h2] = [dt, self.dt_old, self.dt_older].map(c64::real);
+
+        let d1 = (y - self.y_old()) / h0;
+        let d2 = if self.dt_old == 0. {
+            // the first step leaves a point at rest, where the derivative is known
+            (d1 - self.dy_old()) / h0
+        } else {
+            (d1 - (self.y_old() - self.y_older()) / h1) / (h0 + h1)
+        };
+
+        if integration.order() == 1 || self.dt_older == 0. {
+            return Integration::BackwardEuler.error_constant() * 2. * dt * dt * d2.norm();
+        }
+
+        let d1_old = (self.y_old() - self.y_older()) / h1;
+        let d1_older = (self.y_older() - self.y_oldest()) / h2;
+        let d2_old = (d1_old - d1_older) / (h1 + h2);
+        let d3 = (d2 - d2_old) / (h0 + h1 + h2);
+
+        integration.error_constant() * 6. * dt.powi(3) * d3.norm()
+    }
+
+    /// Accepts `y` as the value at the end of the step.
+    pub(crate) fn advance(&mut self, integration: Integration, dt: f64, y: c64) {
+        let (a0, history) = self.derivative(integration, dt);
+        let dy = c64::real(a0) * y + history;
+
+        *self = Self {
+            y_old_re: y.re,
+            y_old_im: y.im,
+            y_older_re: self.y_old_re,
+            y_older_im: self.y_old_im,
+            y_oldest_re: self.y_older_re,
+            y_oldest_im: self.y_older_im,
+            dy_old_re: dy.re,
+            dy_old_im: dy.im,
+            dt_old: dt,
+            dt_older: self.dt_old,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derivative_of_a_parabola() {
+        // y = t^2 sampled at t = 0, 1, 3, so dy/dt = 6 at the end
+        let (y_older, y_old, y) = (c64::ZERO, c64::real(1.), c64::real(9.));
+
+        let (a0, history) = Integration::Gear2.derivative(2., 1., y_old, y_older, c64::real(2.));
+        assert!((c64::real(a0) * y + history - c64::real(6.)).norm() < 1e-12);
+
+        let (a0, history) =
+            Integration::Trapezoidal.derivative(2., 1., y_old, y_older, c64::real(2.));
+        assert!((c64::real(a0) * y + history - c64::real(6.)).norm() < 1e-12);
+
+        let (a0, history) =
+            Integration::BackwardEuler.derivative(2., 1., y_old, y_older, c64::real(2.));
+        assert!((c64::real(a0) * y + history - c64::real(4.)).norm() < 1e-12);
+    }
+}
@@ -0,0 +1,409 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    component::{Component, FromParameters, History, Mode, Parameters},
+    numerical::{LinearEquations, Tolerance, c64},
+};
+
+/// The highest power of `s` a [`TransferFunction`] can have.
+pub const LAPLACE_ORDER: usize = 8;
+
+/// A two-port whose output voltage is `H(s)` times the voltage across its input,
+/// `[out+, out-, in+, in-]`, with `H(s)` the ratio of the polynomials `N0 + N1 s + ...`
+/// and `D0 + D1 s + ...` up to `s^8`. The numerator can't have a higher power than the
+/// denominator, which would make it improper.
+///
+/// Small-signal analysis evaluates `H(jω)` exactly. Transient analysis realizes it as
+/// a state-space system in controllable canonical form, integrated with the circuit.
+/// At DC it settles to `H(0)`, unless the denominator has no constant term, when its
+/// states start from zero.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct TransferFunction {
+    /// Coefficients by ascending power of `s`.
+    pub numerator: [f64; LAPLACE_ORDER + 1],
+    pub denominator: [f64; LAPLACE_ORDER + 1],
+}
+
+impl FromParameters for TransferFunction {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        let numerator = std::array::from_fn(|i| parameters.real_or(&format!("N{i}"), 0.));
+        let supplied: [_; LAPLACE_ORDER + 1] =
+            std::array::from_fn(|i| parameters.take(&format!("D{i}")).map(|d| d.re));
+
+        if supplied.iter().all(|d| d.unwrap_or(0.) == 0.) {
+            match supplied.iter().rposition(Option::is_some) {
+                Some(highest) => parameters.invalid(&format!("D{highest}")),
+                // reports the denominator as missing
+                None => {
+                    parameters.real("D0");
+                }
+            }
+        }
+
+        let denominator = supplied.map(|d| d.unwrap_or(0.));
+
+        let transfer_function = Self {
+            numerator,
+            denominator,
+        };
+
+        if let Some(degree) = numerator.iter().rposition(|&n| n != 0.)
+            && degree > transfer_function.order()
+        {
+            parameters.invalid(&format!("N{degree}"));
+        }
+
+        transfer_function
+    }
+}
+
+impl TransferFunction {
+    /// The highest power of `s` in the denominator, which is how many states it has.
+    pub fn order(&self) -> usize {
+        self.denominator.iter().rposition(|&d| d != 0.).unwrap_or(0)
+    }
+
+    /// `H(s)`.
+    pub fn at(&self, s: c64) -> c64 {
+        let polynomial = |coefficients: &[f64]| {
+            coefficients
+                .iter()
+                .rev()
+                .fold(c64::ZERO, |sum, &coefficient| {
+                    sum * s + c64::real(coefficient)
+                })
+        };
+
+        polynomial(&self.numerator) / polynomial(&self.denominator)
+    }
+
+    /// The denominator coefficient of `s^i`, scaled to make the highest one 1.
+    fn alpha(&self, i: usize) -> f64 {
+        self.denominator[i] / self.denominator[self.order()]
+    }
+
+    /// The part of the output passing straight through.
+    fn feedthrough(&self) -> f64 {
+        self.numerator[self.order()] / self.denominator[self.order()]
+    }
+
+    /// The output with the states `x` and the input `u`.
+    fn output(&self, x: &[f64; LAPLACE_ORDER], u: f64) -> f64 {
+        let d = self.feedthrough();
+        let states = (0..self.order())
+            .map(|i| {
+                (self.numerator[i] / self.denominator[self.order()] - d * self.alpha(i)) * x[i]
+            })
+            .sum::<f64>();
+
+        states + d * u
+    }
+
+    /// The states with the input `u`, where their derivatives are `a0 x + history`.
+    /// At DC both are zero.
+    fn states(&self, a0: f64, history: &[f64; LAPLACE_ORDER], u: f64) -> [f64; LAPLACE_ORDER] {
+        let n = self.order();
+        let mut x = [0.; LAPLACE_ORDER];
+        if n == 0 {
+            return x;
+        }
+
+        // every state is the derivative of the one before, x_i = p_i x_0 + q_i
+        let (mut p, mut q) = ([0.; LAPLACE_ORDER], [0.; LAPLACE_ORDER]);
+        p[0] = 1.;
+        for i in 1..n {
+            p[i] = a0 * p[i - 1];
+            q[i] = a0 * q[i - 1] + history[i - 1];
+        }
+
+        // the derivative of the last one is u - sum(alpha_i x_i)
+        let slope = a0 * p[n - 1] + (0..n).map(|i| self.alpha(i) * p[i]).sum::<f64>();
+        let offset =
+            a0 * q[n - 1] + history[n - 1] + (0..n).map(|i| self.alpha(i) * q[i]).sum::<f64>();
+
+        let x0 = if slope == 0. {
+            0.
+        } else {
+            (u - offset) / slope
+        };
+
+        for i in 0..n {
+            x[i] = p[i] * x0 + q[i];
+        }
+
+        x
+    }
+
+    /// How its states change over a step, `a0` and the history of each of them.
+    fn derivative(&self, mode: Mode, state: &LaplaceState) -> (f64, [f64; LAPLACE_ORDER]) {
+        let Mode::Transient { dt, integration } = mode else {
+            return (0., [0.; LAPLACE_ORDER]);
+        };
+
+        let a0 = state.x[0].derivative(integration, dt).0;
+        let history = state.x.map(|x| x.derivative(integration, dt).1.re);
+
+        (a0, history)
+    }
+
+    /// The output as `gain` times the input plus `offset`, over the step in `mode`.
+    fn response(&self, mode: Mode, state: &LaplaceState) -> (f64, f64) {
+        if let Mode::Initial = mode {
+            // the states are held, only the feedthrough responds to the input
+            let x = state.x.map(|x| x.y_old().re);
+            return (self.feedthrough(), self.output(&x, 0.));
+        }
+
+        let (a0, history) = self.derivative(mode, state);
+
+        let offset = self.output(&self.states(a0, &history, 0.), 0.);
+        let gain = self.output(&self.states(a0, &history, 1.), 1.) - offset;
+
+        (gain, offset)
+    }
+}
+
+/// The states of a [`TransferFunction`] over time.
+#[derive(Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct LaplaceState {
+    x: [History; LAPLACE_ORDER],
+}
+
+impl Component for TransferFunction {
+    type State = LaplaceState;
+    const TERMINAL_COUNT: usize = 5;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(4);
+    const PRIORITY: usize = 20;
+    const PARAMETERS: &[&'static str] = &[
+        "V", "I", "P", "order", "N0", "N1", "N2", "N3", "N4", "N5", "N6", "N7", "N8", "D0", "D1",
+        "D2", "D3", "D4", "D5", "D6", "D7", "D8",
+    ];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 4), (1, 4), (4, 0), (4, 1), (4, 2), (4, 3)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        [op, on, ip, in_, k]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        let (gain, offset) = match mode {
+            Mode::Ac { omega } => (self.at(c64::imag(omega)), c64::ZERO),
+            _ => {
+                let (gain, offset) = self.response(mode, state);
+                (c64::real(gain), c64::real(offset))
+            }
+        };
+
+        net.stamp_voltage_source(op, Some(on), k, offset);
+        net.add_a(k, ip, -gain);
+        net.add_a(k, in_, gain);
+    }
+
+    fn post_stamp(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        [_, _, ip, in_, _]: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+    ) {
+        let u = net.get_voltage_across(ip, in_).re;
+        let (a0, history) = self.derivative(mode, state);
+        let x = self.states(a0, &history, u);
+
+        match mode {
+            Mode::Transient { dt, integration } => {
+                for (state, x) in state.x.iter_mut().zip(x) {
+                    state.advance(integration, dt, c64::real(x));
+                }
+            }
+            Mode::Dc { .. } => state.x = x.map(|x| History::settled(c64::real(x))),
+            Mode::Ac { .. } | Mode::Initial => {}
+        }
+    }
+
+    fn truncation_error(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        [_, _, ip, in_, _]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        tolerance: &Tolerance,
+    ) -> Option<f64> {
+        let Mode::Transient { dt, integration } = mode else {
+            return None;
+        };
+
+        let u = net.get_voltage_across(ip, in_).re;
+        let (a0, history) = self.derivative(mode, state);
+        let x = self.states(a0, &history, u);
+
+        state
+            .x
+            .iter()
+            .zip(x)
+            .take(self.order())
+            .map(|(state, x)| {
+                let x = c64::real(x);
+                let allowed = tolerance.voltage(x.norm().max(state.y_old().norm()));
+                state.truncation_error(integration, dt, x) / allowed
+            })
+            .reduce(f64::max)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [op, on, _, _, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = net.get_voltage_across(op, on);
+        let i = -net.get_branch_current(k);
+
+        match parameter {
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            "order" => Some(c64::real(self.order() as f64)),
+            _ => {
+                let (coefficients, power) = match parameter.split_at_checked(1)? {
+                    ("N", power) => (&self.numerator, power),
+                    ("D", power) => (&self.denominator, power),
+                    _ => return None,
+                };
+
+                let coefficient = coefficients.get(power.parse::<usize>().ok()?)?;
+                Some(c64::real(*coefficient))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::{
+        AcSweep, DcOperatingPoint, Probe, Sweep, Timestep, Transient,
+        circuit::Circuit,
+        component::Integration,
+        numerical::c64,
+        parser::{build, invalid_parameters},
+    };
+
+    /// A second-order low-pass at 1kHz with a Q of 2, `w^2 / (s^2 + w/Q s + w^2)`.
+    fn low_pass(source: &str) -> Circuit {
+        let w = 2. * PI * 1e3;
+        build(&format!(
+            r#"
+            {source}
+            laplace                 "H1"    out gnd in gnd  N0={} D0={} D1={} D2=1
+            ground                          gnd
+            "#,
+            w * w,
+            w * w,
+            w / 2.,
+        ))
+    }
+
+    #[test]
+    fn test_laplace_small_signal() {
+        let mut circuit = low_pass("ac-source-1-terminal in V=1 f=1k");
+
+        let response = AcSweep {
+            sweep: Sweep::Decade { points: 1 },
+            start_hz: 100.,
+            stop_hz: 10e3,
+            probes: vec![Probe::voltage("out")],
+        }
+        .run(&mut circuit)
+        .unwrap();
+
+        let out = response.column("V(out)").unwrap();
+        for (&f, out) in response.frequency.iter().zip(out) {
+            let r = f / 1e3;
+            let expected = c64::ONE / c64::new(1. - r * r, r / 2.);
+            assert!((*out - expected).norm() < 1e-9, "{} at {f}Hz", out.re);
+        }
+
+        // a Q of 2 at the corner, lagging by 90°
+        assert!((out[1].norm() - 2.).abs() < 1e-9);
+        assert!((out[1].arg() + PI / 2.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_laplace_step_response() {
+        let mut circuit = low_pass("pulse-source-1-terminal in V1=0 V2=1 TD=0.1m");
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        assert_eq!(op.voltage("out").unwrap().re, 0.);
+
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: 3e-3,
+            step_s: 1e-6,
+            timestep: Timestep::Fixed,
+            integration: Integration::Trapezoidal,
+            probes: vec![Probe::voltage("out")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+        let out = waveform.column("V(out)").unwrap();
+
+        // underdamped with zeta = 1/4
+        let (w, zeta) = (2. * PI * 1e3, 0.25f64);
+        let wd = w * (1. - zeta * zeta).sqrt();
+        for (&t, out) in waveform.time.iter().zip(out) {
+            let tau = t - 0.1e-3;
+            let expected = if tau <= 0. {
+                0.
+            } else {
+                let decay = (-zeta * w * tau).exp();
+                1. - decay * ((wd * tau).cos() + zeta * w / wd * (wd * tau).sin())
+            };
+
+            // the trapezoidal rule spreads the step over the step it falls in
+            assert!((out.re - expected).abs() < 3e-3, "{} at {t}", out.re);
+        }
+    }
+
+    #[test]
+    fn test_laplace_at_dc() {
+        // 3 (s + 2) / (s^2 + 3s + 1) settles to 6 times its input
+        let mut circuit = build(
+            r#"
+            dc-source-1-terminal            in              V=0.5
+            laplace                 "H1"    out gnd in gnd  N0=6 N1=3 D0=1 D1=3 D2=1
+            ground                          gnd
+            "#,
+        );
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        assert!((op.voltage("out").unwrap().re - 3.).abs() < 1e-12);
+        assert_eq!(op.parameter("H1", "order").unwrap().re, 2.);
+        assert_eq!(op.parameter("H1", "N1").unwrap().re, 3.);
+        assert_eq!(op.parameter("H1", "D2").unwrap().re, 1.);
+        assert_eq!(op.parameter("H1", "D8").unwrap().re, 0.);
+        assert_eq!(op.parameter("H1", "D9"), None);
+
+        // and stays there
+        circuit.step(1e-3).unwrap();
+        assert!((circuit.voltage("out").unwrap().re - 3.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_laplace_improper() {
+        // s^2 / (s + 1) has no state-space realization, and nothing divided by zero
+        for (netlist, invalid) in [
+            ("laplace out gnd in gnd N2=1 D0=1 D1=1", "N2"),
+            ("laplace out gnd in gnd N0=1 D0=0", "D0"),
+            ("laplace out gnd in gnd N0=1 D0=0 D2=0", "D2"),
+        ] {
+            assert_eq!(invalid_parameters(netlist), [invalid], "{netlist}");
+        }
+    }
+}
@@ -1,30 +1,88 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use bytemuck::Pod;
 
+mod behavioral;
+mod controlled;
+mod integration;
+mod laplace;
+mod opamp;
+mod parameters;
 mod passive;
+mod semiconductor;
 mod sources;
+mod switch;
 
+pub use behavioral::*;
+pub use controlled::*;
+pub use integration::*;
+pub use laplace::*;
+pub use opamp::*;
+pub use parameters::*;
 pub use passive::*;
+pub use semiconductor::*;
 pub use sources::*;
+pub use switch::*;
 
 use crate::{
+    buffer::Tables,
     circuit::Circuit,
     expression::Expression,
-    numerical::{LinearEquations, c64},
+    numerical::{LinearEquations, Tolerance, c64},
 };
 
+/// What the components are stamped for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// The DC operating point: capacitors open, inductors shorted and
+    /// independent sources at their DC values, scaled by `source_scale`.
+    Dc { source_scale: f64 },
+    /// A time step of `dt` seconds, solving for the end of the step.
+    Transient { dt: f64, integration: Integration },
+    /// Where a transient starts, like SPICE's `UIC`: capacitor voltages, inductor currents
+    /// and the other reactive states held at where they are, sources at their values there.
+    Initial,
+    /// Small-signal phasors at the angular frequency `omega`, in rad/s.
+    Ac { omega: f64 },
+}
+
+impl Mode {
+    /// The DC operating point with the sources at full value.
+    pub const DC_FULL: Mode = Mode::Dc { source_scale: 1. };
+}
+
+/// Whether a component keeping time in its state by summing up steps has reached `time`
+/// at `t`, up to the rounding of a step cut to land on it.
+pub(crate) fn reached(time: f64, t: f64) -> bool {
+    time <= t + t.abs() * 1e-12
+}
+
 pub trait Component: Pod {
     type State: Pod + Clone + Copy + Default;
+    /// Length of the terminal array handed to the component, branch unknowns included.
     const TERMINAL_COUNT: usize;
+    /// Number of trailing terminals that are not nets but extra MNA unknowns,
+    /// allocated by the circuit for the currents through voltage-defined branches.
+    const BRANCH_COUNT: usize = 0;
+    /// The terminal whose branch unknown is the current into the first terminal and
+    /// through the component, for current-controlled sources to sense it by name.
+    const CURRENT_BRANCH: Option<usize> = None;
+    /// Components are stamped by increasing priority, so that one can overwrite what
+    /// those before it stamped, like [`Ground`] does.
     const PRIORITY: usize;
     const PARAMETERS: &[&'static str] = &[];
     const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0)];
+    /// Whether `stamp` linearizes around an operating point kept in the state by
+    /// [`Component::limit`], which makes the circuit iterate Newton–Raphson.
+    const NONLINEAR: bool = false;
+    /// Like [`Component::NONLINEAR`], but only for the DC operating point, for components
+    /// that are linear within a time step and only change between time points.
+    const NONLINEAR_AT_DC: bool = false;
 
     fn stamp(
         &self,
         le: &mut LinearEquations,
-        dt: f64,
+        mode: Mode,
         terminals: [u32; Self::TERMINAL_COUNT],
         state: &Self::State,
     );
@@ -32,12 +90,60 @@ pub trait Component: Pod {
     fn post_stamp(
         &self,
         _le: &LinearEquations,
-        _dt: f64,
+        _mode: Mode,
         _terminals: [u32; Self::TERMINAL_COUNT],
         _state: &mut Self::State,
     ) {
     }
 
+    /// Refreshes whatever the state caches from the circuit's [`Tables`], once the component
+    /// is put and after every [`Component::post_stamp`]. Components with data that doesn't
+    /// fit a `Pod` keep it there and only look it up here, once per time point.
+    fn load(&self, _tables: &Tables, _state: &mut Self::State) {}
+
+    /// Takes the operating point to linearize around next from the latest Newton–Raphson
+    /// solution into the state, limiting it so that the next iteration stays in reach.
+    /// Returns whether it had to limit, or its currents are further from what the last
+    /// linearization predicted than `tolerance` allows, which keeps the loop from converging yet.
+    fn limit(
+        &self,
+        _le: &LinearEquations,
+        _mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        _state: &mut Self::State,
+        _tolerance: &Tolerance,
+    ) -> bool {
+        false
+    }
+
+    /// Local truncation error of the step just solved relative to what `tolerance` allows,
+    /// so 1 is at the limit. Only components integrating a state over time have one,
+    /// it is read before [`Component::post_stamp`] accepts the step.
+    fn truncation_error(
+        &self,
+        _le: &LinearEquations,
+        _mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        _state: &Self::State,
+        _tolerance: &Tolerance,
+    ) -> Option<f64> {
+        None
+    }
+
+    /// How far into the step just solved the component switches or has a corner,
+    /// if that is before the end of the step. The transient driver then redoes the
+    /// step up to there, so that a time point lands on it. It is read before
+    /// [`Component::post_stamp`] accepts the step.
+    fn breakpoint(
+        &self,
+        _le: &LinearEquations,
+        _mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        _state: &Self::State,
+    ) -> Option<f64> {
+        None
+    }
+
     fn parameter(
         &self,
         _le: &LinearEquations,
@@ -49,26 +155,77 @@ pub trait Component: Pod {
     }
 }
 
+type ErasedConstructor = dyn Fn(
+    &mut Circuit,
+    Option<String>,
+    &[u32],
+    HashMap<String, Expression>,
+) -> Result<(), Vec<ComponentError>>;
+
 pub struct ComponentLibrary {
-    constructors: HashMap<
-        String,
-        Box<
-            dyn Fn(
-                &mut Circuit,
-                HashMap<String, Expression>,
-            ) -> Result<HashMap<String, Expression>, Vec<ComponentError>>,
-        >,
-    >,
+    constructors: HashMap<String, Box<ErasedConstructor>>,
     terminal_counts: HashMap<String, usize>,
+    /// Components that refer to others, and so are constructed after all the rest.
+    deferred: HashSet<String>,
 }
 
-pub struct MissingRequiredParameter {
-    pub parameter: String,
+#[derive(Debug, Clone)]
+pub enum ComponentError {
+    UnusedSuppliedParameter {
+        parameter: String,
+    },
+    MissingRequiredParameter {
+        parameter: String,
+    },
+    /// A supplied parameter has a value the component can't take.
+    InvalidParameter {
+        parameter: String,
+    },
+    /// It was handed a different number of terminals than it has.
+    TerminalCount {
+        expected: usize,
+        supplied: usize,
+    },
+    /// The component whose current it senses isn't in the circuit, or has no branch current.
+    UnknownControl {
+        component: String,
+    },
 }
 
-pub enum ComponentError {
-    UnusedSuppliedParameter { parameter: String },
-    MissingRequiredParameter { parameter: String },
+/// `terminals` as an array of the component's length.
+fn sized<const N: usize>(terminals: &[u32]) -> Result<[u32; N], Vec<ComponentError>> {
+    terminals.try_into().map_err(|_| {
+        vec![ComponentError::TerminalCount {
+            expected: N,
+            supplied: terminals.len(),
+        }]
+    })
+}
+
+/// What a constructor built, unless parameters it needs are missing or invalid,
+/// or it was supplied ones it doesn't take.
+fn checked<C>(
+    constructed: Result<(C, HashMap<String, Expression>), Vec<ComponentError>>,
+) -> Result<C, Vec<ComponentError>> {
+    let (component, rest) = constructed?;
+
+    if !rest.is_empty() {
+        let mut unused: Vec<_> = rest.into_keys().collect();
+        unused.sort();
+
+        return Err(unused
+            .into_iter()
+            .map(|parameter| ComponentError::UnusedSuppliedParameter { parameter })
+            .collect());
+    }
+
+    Ok(component)
+}
+
+impl Default for ComponentLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ComponentLibrary {
@@ -76,29 +233,344 @@ impl ComponentLibrary {
         Self {
             constructors: Default::default(),
             terminal_counts: Default::default(),
+            deferred: Default::default(),
         }
     }
 
+    pub fn with_builtins() -> Self {
+        let mut library = Self::new();
+
+        library
+            .register_default::<Resistor>("resistor")
+            .register_default::<Capacitor>("capacitor")
+            .register_default::<Inductor>("inductor")
+            .register_mutual_inductance("mutual-inductance")
+            .register_default::<IdealTransformer>("transformer")
+            .register_default::<DC1Source>("dc-source-1-terminal")
+            .register_default::<AC1Source>("ac-source-1-terminal")
+            .register_default::<DC2Source>("dc-source-2-terminal")
+            .register_default::<AC2Source>("ac-source-2-terminal")
+            .register_default::<DCCurrentSource>("dc-current-source")
+            .register_default::<ACCurrentSource>("ac-current-source")
+            .register_default::<Pulse1Source>("pulse-source-1-terminal")
+            .register_default::<Pulse2Source>("pulse-source-2-terminal")
+            .register_default::<PulseCurrentSource>("pulse-current-source")
+            .register_component_with_tables("pwl-source-1-terminal", |values, tables| {
+                let mut parameters = Parameters::new(values);
+                let pwl = Pwl::from_parameters(&mut parameters, "V", tables);
+                parameters.finish(Pwl1Source { stimulus: pwl })
+            })
+            .register_component_with_tables("pwl-source-2-terminal", |values, tables| {
+                let mut parameters = Parameters::new(values);
+                let pwl = Pwl::from_parameters(&mut parameters, "V", tables);
+                parameters.finish(Pwl2Source { stimulus: pwl })
+            })
+            .register_component_with_tables("pwl-current-source", |values, tables| {
+                let mut parameters = Parameters::new(values);
+                let pwl = Pwl::from_parameters(&mut parameters, "I", tables);
+                parameters.finish(PwlCurrentSource { stimulus: pwl })
+            })
+            .register_default::<Exp1Source>("exp-source-1-terminal")
+            .register_default::<Exp2Source>("exp-source-2-terminal")
+            .register_default::<Sffm1Source>("sffm-source-1-terminal")
+            .register_default::<Sffm2Source>("sffm-source-2-terminal")
+            .register_default::<Am1Source>("am-source-1-terminal")
+            .register_default::<Am2Source>("am-source-2-terminal")
+            .register_component("square-generator", |values| {
+                let mut parameters = Parameters::new(values);
+                let generator =
+                    FunctionGenerator::from_parameters(&mut parameters, Waveshape::Square);
+                parameters.finish(Stimulus1Source {
+                    stimulus: generator,
+                })
+            })
+            .register_component("triangle-generator", |values| {
+                let mut parameters = Parameters::new(values);
+                let generator =
+                    FunctionGenerator::from_parameters(&mut parameters, Waveshape::Triangle);
+                parameters.finish(Stimulus1Source {
+                    stimulus: generator,
+                })
+            })
+            .register_component("sawtooth-generator", |values| {
+                let mut parameters = Parameters::new(values);
+                let generator =
+                    FunctionGenerator::from_parameters(&mut parameters, Waveshape::Sawtooth);
+                parameters.finish(Stimulus1Source {
+                    stimulus: generator,
+                })
+            })
+            .register_behavioral("behavioral-source", Quantity::Voltage)
+            .register_behavioral("behavioral-current-source", Quantity::Current)
+            .register_default::<VoltageControlledVoltageSource>("vcvs")
+            .register_default::<VoltageControlledCurrentSource>("vccs")
+            .register_current_controlled::<CurrentControlledVoltageSource>("ccvs")
+            .register_current_controlled::<CurrentControlledCurrentSource>("cccs")
+            .register_default::<TransferFunction>("laplace")
+            .register_default::<IdealOpAmp>("ideal-opamp")
+            .register_default::<OpAmp>("opamp")
+            .register_default::<VoltageControlledSwitch>("switch")
+            .register_component_with_tables("timed-switch", |values, tables| {
+                let mut parameters = Parameters::new(values);
+                let switch = TimeControlledSwitch::from_parameters(&mut parameters, tables);
+                parameters.finish(switch)
+            })
+            .register_default::<Diode>("diode")
+            .register_component("zener", |values| {
+                let mut parameters = Parameters::new(values);
+                let model = Diode {
+                    breakdown_volt: parameters.real("BV"),
+                    ..Diode::SILICON
+                };
+                let diode = Diode::from_parameters_or(&mut parameters, model);
+                parameters.finish(diode)
+            })
+            .register_component("led", |values| {
+                let mut parameters = Parameters::new(values);
+                let diode = Diode::from_parameters_or(&mut parameters, Diode::RED_LED);
+                parameters.finish(diode)
+            })
+            .register_component("npn", |values| {
+                let mut parameters = Parameters::new(values);
+                let bjt = Bjt::from_parameters_or(&mut parameters, Bjt::NPN);
+                parameters.finish(bjt)
+            })
+            .register_component("pnp", |values| {
+                let mut parameters = Parameters::new(values);
+                let bjt = Bjt::from_parameters_or(&mut parameters, Bjt::PNP);
+                parameters.finish(bjt)
+            })
+            .register_component("nmos", |values| {
+                let mut parameters = Parameters::new(values);
+                let mosfet = Mosfet::from_parameters_or(&mut parameters, Mosfet::NMOS);
+                parameters.finish(mosfet)
+            })
+            .register_component("pmos", |values| {
+                let mut parameters = Parameters::new(values);
+                let mosfet = Mosfet::from_parameters_or(&mut parameters, Mosfet::PMOS);
+                parameters.finish(mosfet)
+            })
+            .register_default::<Ground>("ground");
+
+        library
+    }
+
     pub fn register_component<C: Component>(
         &mut self,
         name: impl ToString,
         constructor: impl Fn(
             HashMap<String, Expression>,
-        )
-            -> Result<(C, HashMap<String, Expression>), Vec<MissingRequiredParameter>>,
-    ) -> &mut Self {
+        ) -> Result<(C, HashMap<String, Expression>), Vec<ComponentError>>
+        + 'static,
+    ) -> &mut Self
+    where
+        [(); C::TERMINAL_COUNT - C::BRANCH_COUNT]:,
+    {
+        self.register_component_with_tables(name, move |values, _| constructor(values))
+    }
+
+    /// Like [`ComponentLibrary::register_component`], for components that put
+    /// variable-length data into the circuit's [`Tables`].
+    pub fn register_component_with_tables<C: Component>(
+        &mut self,
+        name: impl ToString,
+        constructor: impl Fn(
+            HashMap<String, Expression>,
+            &mut Tables,
+        ) -> Result<(C, HashMap<String, Expression>), Vec<ComponentError>>
+        + 'static,
+    ) -> &mut Self
+    where
+        [(); C::TERMINAL_COUNT - C::BRANCH_COUNT]:,
+    {
+        let name = name.to_string();
+
+        self.terminal_counts
+            .insert(name.to_owned(), C::TERMINAL_COUNT - C::BRANCH_COUNT);
+
+        self.constructors.insert(
+            name,
+            Box::new(move |circuit, name, terminals, parameters| {
+                let terminals = sized(terminals)?;
+                let component = circuit
+                    .tables
+                    .transaction(|tables| checked(constructor(parameters, tables)))?;
+                circuit.put(component, name, terminals);
+
+                Ok(())
+            }),
+        );
+
+        self
+    }
+
+    /// Registers a [`BehavioralSource`] setting `quantity`, with its expression as
+    /// [`Quantity::parameter`].
+    pub fn register_behavioral(&mut self, name: impl ToString, quantity: Quantity) -> &mut Self {
+        let name = name.to_string();
+
+        self.terminal_counts.insert(name.to_owned(), 2);
+
+        self.constructors.insert(
+            name,
+            Box::new(move |circuit, name, terminals, parameters| {
+                let terminals = sized(terminals)?;
+                let mut parameters = Parameters::new(parameters);
+                let expression = parameters.expression(quantity.parameter());
+                let expression = checked(parameters.finish(expression))?;
+
+                let source = BehavioralSource::new(quantity, expression);
+                circuit.put_behavioral(source, name, terminals);
+
+                Ok(())
+            }),
+        );
+
+        self
+    }
+
+    /// Registers a component sensing the current of the one named by its `control`
+    /// parameter, which takes that component's [`Component::CURRENT_BRANCH`] as its last net.
+    /// It is constructed after all the others, so the netlist can name one further down.
+    pub fn register_current_controlled<C: FromParameters>(
+        &mut self,
+        name: impl ToString,
+    ) -> &mut Self
+    where
+        [(); C::TERMINAL_COUNT - C::BRANCH_COUNT]:,
+    {
         let name = name.to_string();
 
         self.terminal_counts
-            .insert(name.to_owned(), C::TERMINAL_COUNT);
+            .insert(name.to_owned(), C::TERMINAL_COUNT - C::BRANCH_COUNT - 1);
+        self.deferred.insert(name.to_owned());
+
+        self.constructors.insert(
+            name,
+            Box::new(|circuit, name, terminals, values| {
+                let nets = C::TERMINAL_COUNT - C::BRANCH_COUNT - 1;
+                if terminals.len() != nets {
+                    return Err(vec![ComponentError::TerminalCount {
+                        expected: nets,
+                        supplied: terminals.len(),
+                    }]);
+                }
+
+                let mut parameters = Parameters::new(values);
+                let control = parameters.name("control");
+                let component = C::from_parameters(&mut parameters);
+                let component = checked(parameters.finish(component))?;
+
+                let branch = circuit
+                    .branch_current_of(&control)
+                    .ok_or(ComponentError::UnknownControl { component: control })
+                    .map_err(|error| vec![error])?;
 
-        self.constructors
-            .insert(name, Box::new(|circuit, hashmap| todo!()));
+                let terminals = sized([terminals, &[branch]].concat().as_slice())?;
+                circuit.put(component, name, terminals);
+
+                Ok(())
+            }),
+        );
+
+        self
+    }
+
+    /// Registers a [`MutualInductance`] coupling the [`Inductor`]s named by its `inductor1`
+    /// and `inductor2` parameters by `K`. Like the current-controlled components, it is
+    /// constructed after all the others.
+    pub fn register_mutual_inductance(&mut self, name: impl ToString) -> &mut Self {
+        let name = name.to_string();
+
+        self.terminal_counts.insert(name.to_owned(), 0);
+        self.deferred.insert(name.to_owned());
+
+        self.constructors.insert(
+            name,
+            Box::new(|circuit, name, terminals, values| {
+                sized::<0>(terminals)?;
+
+                let mut parameters = Parameters::new(values);
+                let inductors =
+                    ["inductor1", "inductor2"].map(|parameter| parameters.name(parameter));
+                if inductors[0] == inductors[1] {
+                    parameters.invalid("inductor2");
+                }
+
+                let coupling = parameters.real("K");
+                if !(-1. ..=1.).contains(&coupling) {
+                    parameters.invalid("K");
+                }
+
+                let coupling = checked(parameters.finish(coupling))?;
+
+                let mut errors = vec![];
+                let mut coupled = vec![];
+
+                for (parameter, inductor) in ["inductor1", "inductor2"].into_iter().zip(inductors) {
+                    let branch = circuit.branch_current_of(&inductor);
+
+                    match circuit.component::<Inductor>(&inductor).zip(branch) {
+                        Some((inductor, _))
+                            if inductor.inductance_h.is_nan() || inductor.inductance_h <= 0. =>
+                        {
+                            errors.push(ComponentError::InvalidParameter {
+                                parameter: parameter.to_string(),
+                            })
+                        }
+                        Some((inductor, k)) => coupled.push((k, inductor.inductance_h)),
+                        None => errors.push(ComponentError::UnknownControl {
+                            component: inductor,
+                        }),
+                    }
+                }
+
+                let [(k1, inductance_1_h), (k2, inductance_2_h)] = coupled[..] else {
+                    return Err(errors);
+                };
+
+                let mutual = MutualInductance {
+                    inductance_1_h,
+                    inductance_2_h,
+                    coupling,
+                };
+                circuit.put(mutual, name, [k1, k2]);
+
+                Ok(())
+            }),
+        );
 
         self
     }
 
+    pub fn register_default<C: FromParameters>(&mut self, name: impl ToString) -> &mut Self
+    where
+        [(); C::TERMINAL_COUNT - C::BRANCH_COUNT]:,
+    {
+        self.register_component(name, C::construct)
+    }
+
+    /// Constructs the component registered as `component_name` and puts it into the circuit.
+    /// Returns `None` if no such component was registered.
+    pub fn construct(
+        &self,
+        component_name: &str,
+        circuit: &mut Circuit,
+        name: Option<String>,
+        terminals: &[u32],
+        parameters: HashMap<String, Expression>,
+    ) -> Option<Result<(), Vec<ComponentError>>> {
+        let constructor = self.constructors.get(component_name)?;
+        Some(constructor(circuit, name, terminals, parameters))
+    }
+
     pub fn terminal_count_of(&self, component_name: &str) -> Option<usize> {
         self.terminal_counts.get(component_name).copied()
     }
+
+    /// Whether the component registered as `component_name` refers to others,
+    /// so that it has to be constructed once they are all in the circuit.
+    pub fn is_deferred(&self, component_name: &str) -> bool {
+        self.deferred.contains(component_name)
+    }
 }
@@ -0,0 +1,450 @@
+use std::f64::consts::PI;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    component::{Component, FromParameters, History, Mode, Parameters},
+    numerical::{LinearEquations, Tolerance, c64},
+};
+
+// Op-amps share the terminal layout [in+, in-, out], the output driving
+// against ground through a branch unknown.
+
+/// The nullor: the output takes whatever current holds `v(in+) = v(in-)`,
+/// which needs negative feedback to have a solution.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct IdealOpAmp;
+
+impl FromParameters for IdealOpAmp {
+    fn from_parameters(_: &mut Parameters) -> Self {
+        IdealOpAmp
+    }
+}
+
+impl Component for IdealOpAmp {
+    type State = ();
+    const TERMINAL_COUNT: usize = 4;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(3);
+    const PRIORITY: usize = 20;
+    const PARAMETERS: &[&'static str] = &["V", "I", "P"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(3, 0), (3, 1), (2, 3)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        _: Mode,
+        [ip, in_, out, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+    ) {
+        // the nullator across the inputs, the norator from ground to the output
+        net.add_a(k, ip, c64::ONE);
+        net.add_a(k, in_, -c64::ONE);
+        net.add_a(out, k, c64::ONE);
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [_, _, out, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = net.x[out as usize];
+        let i = -net.get_branch_current(k);
+
+        match parameter {
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            _ => None,
+        }
+    }
+}
+
+/// Where an [`OpAmp`] operates, reported as the `region` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpAmpRegion {
+    Linear = 0,
+    /// Its output changes as fast as the slew rate allows.
+    Slewing = 1,
+    /// Its output sits at one of the rails.
+    Saturated = 2,
+}
+
+/// Macro model with the open-loop gain `A` rolling off from a single pole to cross
+/// unity at `GBW`, behind an output resistance `Rout`. The pole voltage is limited to
+/// the rails `Vmin` and `Vmax` and changes no faster than the slew rate `SR`, in V/s.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct OpAmp {
+    pub gain: f64,
+    pub gain_bandwidth_hz: f64,
+    pub output_resistance_ohm: f64,
+    pub max_output_volt: f64,
+    pub min_output_volt: f64,
+    pub slew_rate: f64,
+}
+
+impl Default for OpAmp {
+    fn default() -> Self {
+        Self {
+            gain: 1e5,
+            gain_bandwidth_hz: 1e6,
+            output_resistance_ohm: 75.,
+            max_output_volt: 15.,
+            min_output_volt: -15.,
+            slew_rate: 0.5e6,
+        }
+    }
+}
+
+impl FromParameters for OpAmp {
+    /// The gain, gain-bandwidth product and slew rate have to be positive, the output
+    /// resistance can't be negative and `Vmax` has to be above `Vmin`.
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        let default = Self::default();
+
+        let opamp = Self {
+            gain: parameters.real_or("A", default.gain),
+            gain_bandwidth_hz: parameters.real_or("GBW", default.gain_bandwidth_hz),
+            output_resistance_ohm: parameters.real_or("Rout", default.output_resistance_ohm),
+            max_output_volt: parameters.real_or("Vmax", default.max_output_volt),
+            min_output_volt: parameters.real_or("Vmin", default.min_output_volt),
+            slew_rate: parameters.real_or("SR", default.slew_rate),
+        };
+
+        for (parameter, value) in [
+            ("A", opamp.gain),
+            ("GBW", opamp.gain_bandwidth_hz),
+            ("SR", opamp.slew_rate),
+        ] {
+            if value.is_nan() || value <= 0. {
+                parameters.invalid(parameter);
+            }
+        }
+
+        let resistance_ohm = opamp.output_resistance_ohm;
+        if !(resistance_ohm.is_finite() && resistance_ohm >= 0.) {
+            parameters.invalid("Rout");
+        }
+
+        let rails = [opamp.min_output_volt, opamp.max_output_volt];
+        if rails.iter().any(|v| v.is_nan()) || rails[0] >= rails[1] {
+            parameters.invalid("Vmax");
+        }
+
+        opamp
+    }
+}
+
+impl OpAmp {
+    /// Time constant of the dominant pole.
+    fn tau(&self) -> f64 {
+        self.gain / (2. * PI * self.gain_bandwidth_hz)
+    }
+
+    /// Where the pole voltage goes with `vd` across the inputs, and what it is held at
+    /// unless it is linear.
+    fn operate(&self, mode: Mode, vd: f64, v: &History) -> (OpAmpRegion, f64) {
+        let (v_linear, slew) = match mode {
+            Mode::Transient { dt, integration } => {
+                // tau dv/dt + v = A vd, with dv/dt = a0 v + history
+                let (a0, history) = v.derivative(integration, dt);
+                let v_linear = (self.gain * vd - self.tau() * history.re) / (self.tau() * a0 + 1.);
+                let slew = [-self.slew_rate, self.slew_rate].map(|dv| (dv - history.re) / a0);
+                (v_linear, Some(slew))
+            }
+            Mode::Initial => (v.y_old().re, None),
+            _ => (self.gain * vd, None),
+        };
+
+        let v_slewed = match slew {
+            Some([low, high]) => v_linear.clamp(low, high),
+            None => v_linear,
+        };
+        let v_limited = v_slewed.clamp(self.min_output_volt, self.max_output_volt);
+
+        if v_limited != v_slewed {
+            (OpAmpRegion::Saturated, v_limited)
+        } else if v_slewed != v_linear {
+            (OpAmpRegion::Slewing, v_slewed)
+        } else {
+            (OpAmpRegion::Linear, v_linear)
+        }
+    }
+}
+
+/// The pole voltage over time, and the region and held voltage it is linearized for.
+#[derive(Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct OpAmpState {
+    v: History,
+    region: u64,
+    v_held: f64,
+}
+
+impl OpAmpState {
+    fn region(&self) -> OpAmpRegion {
+        match self.region {
+            1 => OpAmpRegion::Slewing,
+            2 => OpAmpRegion::Saturated,
+            _ => OpAmpRegion::Linear,
+        }
+    }
+}
+
+impl Component for OpAmp {
+    type State = OpAmpState;
+    /// The inputs and output, then the pole voltage and the output current.
+    const TERMINAL_COUNT: usize = 5;
+    const BRANCH_COUNT: usize = 2;
+    const CURRENT_BRANCH: Option<usize> = Some(4);
+    const PRIORITY: usize = 20;
+    const PARAMETERS: &[&'static str] = &[
+        "A", "GBW", "Rout", "Vmax", "Vmin", "SR", "V", "I", "P", "region",
+    ];
+    const ACTIVE_TERMINALS: &[(usize, usize)] =
+        &[(3, 0), (3, 1), (3, 3), (4, 2), (4, 3), (4, 4), (2, 4)];
+    const NONLINEAR: bool = true;
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        [ip, in_, out, kv, ki]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        let gain = c64::real(self.gain);
+
+        match (state.region(), mode) {
+            // held where a transient starts, whatever the region
+            (_, Mode::Initial) => {
+                net.add_a(kv, kv, c64::ONE);
+                net.add_b(kv, state.v.y_old());
+            }
+            (OpAmpRegion::Linear, Mode::Transient { dt, integration }) => {
+                let (a0, history) = state.v.derivative(integration, dt);
+                net.add_a(kv, kv, c64::real(self.tau() * a0 + 1.));
+                net.add_b(kv, -c64::real(self.tau()) * history);
+            }
+            (OpAmpRegion::Linear, Mode::Ac { omega }) => {
+                net.add_a(kv, kv, c64::new(1., omega * self.tau()));
+            }
+            (OpAmpRegion::Linear, Mode::Dc { .. }) => net.add_a(kv, kv, c64::ONE),
+            // held, so it doesn't respond to small signals
+            (_, Mode::Ac { .. }) => net.add_a(kv, kv, c64::ONE),
+            (_, _) => {
+                net.add_a(kv, kv, c64::ONE);
+                net.add_b(kv, c64::real(state.v_held));
+            }
+        }
+
+        if state.region() == OpAmpRegion::Linear && mode != Mode::Initial {
+            net.add_a(kv, ip, -gain);
+            net.add_a(kv, in_, gain);
+        }
+
+        // v(out) = v + Rout i, with i flowing into the output
+        net.add_a(ki, out, c64::ONE);
+        net.add_a(ki, kv, -c64::ONE);
+        net.add_a(ki, ki, -c64::real(self.output_resistance_ohm));
+        net.add_a(out, ki, c64::ONE);
+    }
+
+    fn post_stamp(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        [_, _, _, kv, _]: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+    ) {
+        let v = net.x[kv as usize];
+
+        match mode {
+            Mode::Transient { dt, integration } => state.v.advance(integration, dt, v),
+            Mode::Dc { .. } => state.v = History::settled(v),
+            Mode::Ac { .. } | Mode::Initial => {}
+        }
+    }
+
+    fn limit(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        [ip, in_, _, _, _]: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+        _: &Tolerance,
+    ) -> bool {
+        let (region, v_held) = self.operate(mode, net.get_voltage_across(ip, in_).re, &state.v);
+        let unsettled = region != state.region();
+
+        state.region = region as u64;
+        state.v_held = v_held;
+
+        unsettled
+    }
+
+    fn truncation_error(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        [_, _, _, kv, _]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        tolerance: &Tolerance,
+    ) -> Option<f64> {
+        let Mode::Transient { dt, integration } = mode else {
+            return None;
+        };
+
+        let v = net.x[kv as usize];
+        let allowed = tolerance.voltage(v.norm().max(state.v.y_old().norm()));
+        Some(state.v.truncation_error(integration, dt, v) / allowed)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [_, _, out, _, ki]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = net.x[out as usize];
+        let i = -net.get_branch_current(ki);
+
+        match parameter {
+            "A" => Some(c64::real(self.gain)),
+            "GBW" => Some(c64::real(self.gain_bandwidth_hz)),
+            "Rout" => Some(c64::real(self.output_resistance_ohm)),
+            "Vmax" => Some(c64::real(self.max_output_volt)),
+            "Vmin" => Some(c64::real(self.min_output_volt)),
+            "SR" => Some(c64::real(self.slew_rate)),
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            "region" => Some(c64::real(state.region() as u8 as f64)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AcSweep, DcOperatingPoint, Probe, Sweep, Timestep, Transient,
+        circuit::Circuit,
+        component::Integration,
+        parser::{build, invalid_parameters},
+    };
+
+    fn inverting_amplifier(opamp: &str, input_volt: f64) -> Circuit {
+        build(&format!(
+            r#"
+            dc-source-1-terminal            in          V={input_volt}
+            resistor                        in inv      R=1k
+            resistor                        inv out     R=10k
+            {opamp}
+            ground                          gnd
+            "#
+        ))
+    }
+
+    #[test]
+    fn test_inverting_amplifier() {
+        let mut circuit = inverting_amplifier(r#"ideal-opamp "U1" gnd inv out"#, 0.5);
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        assert!((op.voltage("out").unwrap().re + 5.).abs() < 1e-9);
+        assert!(op.voltage("inv").unwrap().re.abs() < 1e-12);
+        assert!((op.parameter("U1", "I").unwrap().re + 0.5e-3).abs() < 1e-12);
+
+        // the finite gain leaves a small error
+        let mut circuit = inverting_amplifier(r#"opamp "U1" gnd inv out"#, 0.5);
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        let out = op.voltage("out").unwrap().re;
+        assert!((out + 5.).abs() < 1e-3 && out > -5., "{}", out);
+        assert_eq!(op.parameter("U1", "region").unwrap().re, 0.);
+
+        // and the rails clip the output
+        let mut circuit = inverting_amplifier(r#"opamp "U1" gnd inv out Vmin=-12"#, 2.);
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        let out = op.voltage("out").unwrap().re;
+        assert!((out + 12.).abs() < 0.1, "{}", out);
+        assert_eq!(op.parameter("U1", "region").unwrap().re, 2.);
+    }
+
+    #[test]
+    fn test_follower_bandwidth() {
+        let mut circuit = build(
+            r#"
+            ac-source-1-terminal            in          V=1 f=0
+            opamp                   "U1"    in out out  GBW=1M
+            ground                          gnd
+            "#,
+        );
+
+        // the loop closes the pole at the gain-bandwidth product, around the operating
+        // point the sweep finds first
+        let response = AcSweep {
+            sweep: Sweep::Linear { points: 2 },
+            start_hz: 1e3,
+            stop_hz: 1e6,
+            probes: vec![Probe::voltage("out")],
+        }
+        .run(&mut circuit)
+        .unwrap();
+
+        let out = response.column("V(out)").unwrap();
+        assert!((out[0].norm() - 1.).abs() < 1e-4, "{:?}", out[0]);
+        assert!((out[1].norm() - 0.5f64.sqrt()).abs() < 1e-4, "{:?}", out[1]);
+        assert!(
+            (out[1].arg().to_degrees() + 45.).abs() < 1e-2,
+            "{:?}",
+            out[1]
+        );
+    }
+
+    #[test]
+    fn test_slew_rate() {
+        let mut circuit = build(
+            r#"
+            dc-source-1-terminal            in          V=1
+            opamp                   "U1"    in out out  SR=0.5M
+            ground                          gnd
+            "#,
+        );
+
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: 20e-6,
+            step_s: 0.1e-6,
+            timestep: Timestep::Fixed,
+            integration: Integration::BackwardEuler,
+            probes: vec![Probe::voltage("out"), Probe::parameter("U1", "region")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+        let out = waveform.column("V(out)").unwrap();
+        let region = waveform.column("region(U1)").unwrap();
+
+        // ramps at 0.5V/us, then settles on the input
+        assert!((out[10].re - 0.5).abs() < 1e-9, "{}", out[10].re);
+        assert_eq!(region[10].re, 1.);
+        assert!((out.last().unwrap().re - 1.).abs() < 1e-4);
+        assert_eq!(region.last().unwrap().re, 0.);
+    }
+    #[test]
+    fn test_invalid_parameters() {
+        assert_eq!(
+            invalid_parameters("opamp a b c A=0 GBW=-1M Rout=-1 SR=-1"),
+            ["A", "GBW", "SR", "Rout"]
+        );
+        // the rails are the wrong way around, or the same
+        for rails in ["Vmin=5 Vmax=-5", "Vmin=5 Vmax=5"] {
+            assert_eq!(
+                invalid_parameters(&format!("opamp a b c {rails}")),
+                ["Vmax"]
+            );
+        }
+    }
+}
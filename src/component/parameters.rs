@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use crate::{
+    component::{Component, ComponentError},
+    expression::Expression,
+    numerical::c64,
+};
+
+/// Supplied netlist parameters that a constructor takes values out of.
+/// Whatever is left over once the component is built is reported as unused.
+pub struct Parameters {
+    values: HashMap<String, Expression>,
+    errors: Vec<ComponentError>,
+}
+
+impl Parameters {
+    pub fn new(values: HashMap<String, Expression>) -> Self {
+        Self {
+            values,
+            errors: vec![],
+        }
+    }
+
+    /// Takes a constant value, reporting one that depends on anything as invalid.
+    pub fn take(&mut self, parameter: &str) -> Option<c64> {
+        let value = self.values.remove(parameter)?.compute_fixed();
+
+        if value.is_none() {
+            self.invalid(parameter);
+        }
+
+        value
+    }
+
+    /// Takes the expression as written, for components that evaluate it themselves.
+    pub fn expression(&mut self, parameter: &str) -> Expression {
+        self.values.remove(parameter).unwrap_or_else(|| {
+            self.missing(parameter);
+
+            Expression::Real(0.)
+        })
+    }
+
+    /// Takes the name of another component, written quoted like `control="V1"`.
+    pub fn name(&mut self, parameter: &str) -> String {
+        match self.values.remove(parameter) {
+            Some(Expression::Variable {
+                name,
+                subscript: None,
+            }) => name,
+            Some(_) => {
+                self.invalid(parameter);
+
+                String::new()
+            }
+            None => {
+                self.missing(parameter);
+
+                String::new()
+            }
+        }
+    }
+
+    pub fn complex(&mut self, parameter: &str) -> c64 {
+        if !self.values.contains_key(parameter) {
+            self.missing(parameter);
+        }
+
+        self.take(parameter).unwrap_or(c64::ZERO)
+    }
+
+    /// Takes a real value, reporting one with an imaginary part as invalid.
+    pub fn real(&mut self, parameter: &str) -> f64 {
+        let value = self.complex(parameter);
+        self.real_part(parameter, value)
+    }
+
+    pub fn real_or(&mut self, parameter: &str, default: f64) -> f64 {
+        match self.take(parameter) {
+            Some(value) => self.real_part(parameter, value),
+            None => default,
+        }
+    }
+
+    fn real_part(&mut self, parameter: &str, value: c64) -> f64 {
+        if value.im != 0. {
+            self.invalid(parameter);
+        }
+
+        value.re
+    }
+
+    fn missing(&mut self, parameter: &str) {
+        self.errors.push(ComponentError::MissingRequiredParameter {
+            parameter: parameter.to_string(),
+        });
+    }
+
+    /// Reports the value taken for `parameter` as one the component can't have,
+    /// unless it is missing or invalid, which is reported already.
+    pub fn invalid(&mut self, parameter: &str) {
+        let reported = self.errors.iter().any(|error| match error {
+            ComponentError::MissingRequiredParameter { parameter: p }
+            | ComponentError::InvalidParameter { parameter: p } => p == parameter,
+            _ => false,
+        });
+
+        if !reported {
+            self.errors.push(ComponentError::InvalidParameter {
+                parameter: parameter.to_string(),
+            });
+        }
+    }
+
+    pub fn finish<C>(
+        self,
+        component: C,
+    ) -> Result<(C, HashMap<String, Expression>), Vec<ComponentError>> {
+        if self.errors.is_empty() {
+            Ok((component, self.values))
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+/// A component that can be built from netlist parameters alone,
+/// named after the entries of its [`Component::PARAMETERS`].
+pub trait FromParameters: Component {
+    fn from_parameters(parameters: &mut Parameters) -> Self;
+
+    fn construct(
+        values: HashMap<String, Expression>,
+    ) -> Result<(Self, HashMap<String, Expression>), Vec<ComponentError>> {
+        let mut parameters = Parameters::new(values);
+        let component = Self::from_parameters(&mut parameters);
+        parameters.finish(component)
+    }
+}
@@ -1,8 +1,8 @@
 use bytemuck::{Pod, Zeroable};
 
 use crate::{
-    component::Component,
-    numerical::{LinearEquations, c64},
+    component::{Component, FromParameters, History, Mode, Parameters},
+    numerical::{LinearEquations, Tolerance, c64},
 };
 
 #[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
@@ -11,6 +11,14 @@ pub struct Resistor {
     pub resistance_ohm: f64,
 }
 
+impl FromParameters for Resistor {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            resistance_ohm: parameters.real("R"),
+        }
+    }
+}
+
 impl Component for Resistor {
     type State = ();
     const TERMINAL_COUNT: usize = 2;
@@ -18,7 +26,7 @@ impl Component for Resistor {
     const PARAMETERS: &[&'static str] = &["R", "V", "I", "P"];
     const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (0, 1), (1, 0), (1, 1)];
 
-    fn stamp(&self, net: &mut LinearEquations, _: f64, [n1, n2]: [u32; 2], _: &Self::State) {
+    fn stamp(&self, net: &mut LinearEquations, _: Mode, [n1, n2]: [u32; 2], _: &Self::State) {
         let y = c64::new(1. / self.resistance_ohm, 0.);
 
         net.add_a(n1, n1, y);
@@ -47,168 +55,551 @@ impl Component for Resistor {
     }
 }
 
+/// Its current is a branch unknown, so that its voltage can be held where a transient starts.
 #[derive(Pod, Zeroable, Clone, Copy, Default)]
 #[repr(C)]
 pub struct Capacitor {
     pub capacitance_f: f64,
 }
 
+impl FromParameters for Capacitor {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            capacitance_f: parameters.real("C"),
+        }
+    }
+}
+
 #[derive(Pod, Zeroable, Clone, Copy, Default)]
 #[repr(C)]
 pub struct CapacitorState {
-    v_old_re: f64,
-    v_old_im: f64,
-    dv_per_dt_re: f64,
-    dv_per_dt_im: f64,
+    v: History,
 }
 
 impl Component for Capacitor {
     type State = CapacitorState;
 
-    const TERMINAL_COUNT: usize = 2;
+    const TERMINAL_COUNT: usize = 3;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(2);
     const PRIORITY: usize = 10;
     const PARAMETERS: &[&'static str] = &["C", "V", "I", "P"];
-    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (0, 1), (1, 0), (1, 1)];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 2), (1, 2), (2, 0), (2, 1), (2, 2)];
 
     fn stamp(
         &self,
         net: &mut LinearEquations,
-        dt: f64,
-        [n1, n2]: [u32; Self::TERMINAL_COUNT],
+        mode: Mode,
+        [n1, n2, k]: [u32; Self::TERMINAL_COUNT],
         state: &Self::State,
     ) {
-        let g_eq = c64::new(self.capacitance_f / dt, 0.);
-        let v_prev = c64::new(state.v_old_re, state.v_old_im);
-        let i_hist = g_eq * v_prev;
-
-        net.add_a(n1, n1, g_eq);
-        net.add_a(n1, n2, -g_eq);
-        net.add_a(n2, n1, -g_eq);
-        net.add_a(n2, n2, g_eq);
-
-        net.add_b(n1, i_hist);
-        net.add_b(n2, -i_hist);
+        // i = C dv/dt = g v + i_hist
+        let (g_eq, i_hist) = match mode {
+            Mode::Transient { dt, integration } => {
+                let (a0, history) = state.v.derivative(integration, dt);
+                let c = c64::real(self.capacitance_f);
+                (c * c64::real(a0), c * history)
+            }
+            Mode::Ac { omega } => (c64::imag(omega * self.capacitance_f), c64::ZERO),
+            Mode::Dc { .. } => (c64::ZERO, c64::ZERO),
+            Mode::Initial => {
+                net.stamp_voltage_source(n1, Some(n2), k, state.v.y_old());
+                return;
+            }
+        };
+
+        net.add_a(n1, k, c64::ONE);
+        net.add_a(n2, k, -c64::ONE);
+
+        net.add_a(k, n1, g_eq);
+        net.add_a(k, n2, -g_eq);
+        net.add_a(k, k, -c64::ONE);
+        net.add_b(k, -i_hist);
     }
 
     fn post_stamp(
         &self,
         net: &LinearEquations,
-        dt: f64,
-        [n1, n2]: [u32; Self::TERMINAL_COUNT],
+        mode: Mode,
+        [n1, n2, _]: [u32; Self::TERMINAL_COUNT],
         state: &mut Self::State,
     ) {
         let v = net.get_voltage_across(n1, n2);
-        state.dv_per_dt_re = (v.re - state.v_old_re) / dt;
-        state.dv_per_dt_im = (v.im - state.v_old_im) / dt;
-        state.v_old_re = v.re;
-        state.v_old_im = v.im;
+
+        match mode {
+            Mode::Transient { dt, integration } => state.v.advance(integration, dt, v),
+            // the operating point is where a transient starts from, at rest
+            Mode::Dc { .. } => state.v = History::settled(v),
+            Mode::Ac { .. } | Mode::Initial => {}
+        }
     }
 
-    fn parameter(
+    fn truncation_error(
         &self,
         net: &LinearEquations,
-        [start, end]: [u32; Self::TERMINAL_COUNT],
+        mode: Mode,
+        [n1, n2, _]: [u32; Self::TERMINAL_COUNT],
         state: &Self::State,
+        tolerance: &Tolerance,
+    ) -> Option<f64> {
+        let Mode::Transient { dt, integration } = mode else {
+            return None;
+        };
+
+        let v = net.get_voltage_across(n1, n2);
+        let allowed = tolerance.voltage(v.norm().max(state.v.y_old().norm()));
+        Some(state.v.truncation_error(integration, dt, v) / allowed)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [start, end, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
         parameter: &str,
     ) -> Option<c64> {
         let v = net.get_voltage_across(start, end);
-        let v_prev = c64::new(state.v_old_re, state.v_old_im);
-        let dv_per_dt = c64::new(state.dv_per_dt_re, state.dv_per_dt_im);
-
-        let g_eq = c64::new(self.capacitance_f, 0.) / c64::new(1., 0.);
-        let i = g_eq * v_prev;
+        let i = net.get_branch_current(k);
 
         match parameter {
             "C" => Some(c64::new(self.capacitance_f, 0.)),
             "V" => Some(v),
             "I" => Some(i),
-            "P" => Some(v * i * dv_per_dt),
+            "P" => Some(v * i),
             _ => None,
         }
     }
 }
 
+/// Its current is a branch unknown, so that it can be a short at DC.
 #[derive(Pod, Zeroable, Clone, Copy, Default)]
 #[repr(C)]
 pub struct Inductor {
     pub inductance_h: f64,
 }
 
+impl FromParameters for Inductor {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            inductance_h: parameters.real("L"),
+        }
+    }
+}
+
 #[derive(Pod, Zeroable, Clone, Copy, Default)]
 #[repr(C)]
 pub struct InductorState {
-    i_old_re: f64,
-    i_old_im: f64,
-    di_per_dt_re: f64,
-    di_per_dt_im: f64,
+    i: History,
 }
 
 impl Component for Inductor {
     type State = InductorState;
 
-    const TERMINAL_COUNT: usize = 2;
+    const TERMINAL_COUNT: usize = 3;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(2);
     const PRIORITY: usize = 10;
     const PARAMETERS: &[&'static str] = &["L", "V", "I", "P"];
-    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (0, 1), (1, 0), (1, 1)];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 2), (1, 2), (2, 0), (2, 1), (2, 2)];
 
     fn stamp(
         &self,
         net: &mut LinearEquations,
-        dt: f64,
-        [n1, n2]: [u32; Self::TERMINAL_COUNT],
+        mode: Mode,
+        [n1, n2, k]: [u32; Self::TERMINAL_COUNT],
         state: &Self::State,
     ) {
-        let g_eq = c64::new(dt / self.inductance_h, 0.);
-        let i_prev = c64::new(state.i_old_re, state.i_old_im);
+        // v(n1) - v(n2) = L di/dt = z i + v_hist
+        let (z_eq, v_hist) = match mode {
+            Mode::Transient { dt, integration } => {
+                let (a0, history) = state.i.derivative(integration, dt);
+                let l = c64::real(self.inductance_h);
+                (l * c64::real(a0), l * history)
+            }
+            Mode::Ac { omega } => (c64::imag(omega * self.inductance_h), c64::ZERO),
+            Mode::Dc { .. } => (c64::ZERO, c64::ZERO),
+            Mode::Initial => {
+                net.add_a(n1, k, c64::ONE);
+                net.add_a(n2, k, -c64::ONE);
+                net.add_a(k, k, c64::ONE);
+                net.add_b(k, state.i.y_old());
+                return;
+            }
+        };
+
+        net.stamp_voltage_source(n1, Some(n2), k, v_hist);
+        net.add_a(k, k, -z_eq);
+    }
+
+    fn post_stamp(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        [_, _, k]: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+    ) {
+        let i = net.get_branch_current(k);
+
+        match mode {
+            Mode::Transient { dt, integration } => state.i.advance(integration, dt, i),
+            Mode::Dc { .. } => state.i = History::settled(i),
+            Mode::Ac { .. } | Mode::Initial => {}
+        }
+    }
+
+    fn truncation_error(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        [_, _, k]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        tolerance: &Tolerance,
+    ) -> Option<f64> {
+        let Mode::Transient { dt, integration } = mode else {
+            return None;
+        };
+
+        let i = net.get_branch_current(k);
+        let allowed = tolerance.current(i.norm().max(state.i.y_old().norm()));
+        Some(state.i.truncation_error(integration, dt, i) / allowed)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [start, end, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = net.get_voltage_across(start, end);
+        let i = net.get_branch_current(k);
+
+        match parameter {
+            "L" => Some(c64::new(self.inductance_h, 0.)),
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            _ => None,
+        }
+    }
+}
+
+/// SPICE's `K`: couples two [`Inductor`]s already in the circuit by `K`, so that each
+/// sees the mutual inductance `M = K sqrt(L1 L2)` times the rate of change of the other's
+/// current. Its terminals are the inductors' branch unknowns, and both are dotted at
+/// their positive terminal.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct MutualInductance {
+    pub inductance_1_h: f64,
+    pub inductance_2_h: f64,
+    /// Between -1 and 1, where 1 leaves no leakage inductance.
+    pub coupling: f64,
+}
 
-        let i_hist = i_prev;
+impl MutualInductance {
+    pub fn mutual_inductance_h(&self) -> f64 {
+        self.coupling * (self.inductance_1_h * self.inductance_2_h).sqrt()
+    }
+}
 
-        net.add_a(n1, n1, g_eq);
-        net.add_a(n1, n2, -g_eq);
-        net.add_a(n2, n1, -g_eq);
-        net.add_a(n2, n2, g_eq);
+#[derive(Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct MutualInductanceState {
+    i1: History,
+    i2: History,
+}
 
-        net.add_b(n1, -i_hist);
-        net.add_b(n2, i_hist);
+impl Component for MutualInductance {
+    type State = MutualInductanceState;
+
+    const TERMINAL_COUNT: usize = 2;
+    // after the inductors, whose rows it adds to
+    const PRIORITY: usize = 20;
+    const PARAMETERS: &[&'static str] = &["K", "M"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 1), (1, 0)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        [k1, k2]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        let m = c64::real(self.mutual_inductance_h());
+
+        // adds M di2/dt to the voltage across the first and M di1/dt to the second
+        match mode {
+            Mode::Transient { dt, integration } => {
+                let (a0, history_1) = state.i1.derivative(integration, dt);
+                let (_, history_2) = state.i2.derivative(integration, dt);
+
+                net.add_a(k1, k2, -m * c64::real(a0));
+                net.add_a(k2, k1, -m * c64::real(a0));
+                net.add_b(k1, m * history_2);
+                net.add_b(k2, m * history_1);
+            }
+            Mode::Ac { omega } => {
+                net.add_a(k1, k2, -m * c64::imag(omega));
+                net.add_a(k2, k1, -m * c64::imag(omega));
+            }
+            // the inductors hold their currents where a transient starts
+            Mode::Dc { .. } | Mode::Initial => {}
+        }
     }
 
     fn post_stamp(
         &self,
         net: &LinearEquations,
-        dt: f64,
-        [n1, n2]: [u32; Self::TERMINAL_COUNT],
+        mode: Mode,
+        [k1, k2]: [u32; Self::TERMINAL_COUNT],
         state: &mut Self::State,
     ) {
-        let v = net.get_voltage_across(n1, n2);
+        let (i1, i2) = (net.get_branch_current(k1), net.get_branch_current(k2));
+
+        match mode {
+            Mode::Transient { dt, integration } => {
+                state.i1.advance(integration, dt, i1);
+                state.i2.advance(integration, dt, i2);
+            }
+            Mode::Dc { .. } => {
+                state.i1 = History::settled(i1);
+                state.i2 = History::settled(i2);
+            }
+            Mode::Ac { .. } | Mode::Initial => {}
+        }
+    }
 
-        let di_dt_re = v.re / self.inductance_h;
-        let di_dt_im = v.im / self.inductance_h;
+    fn parameter(
+        &self,
+        _: &LinearEquations,
+        _: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        match parameter {
+            "K" => Some(c64::real(self.coupling)),
+            "M" => Some(c64::real(self.mutual_inductance_h())),
+            _ => None,
+        }
+    }
+}
+
+/// Lossless transformer with the turns ratio `n = N1 / N2` between the windings
+/// `[p1, n1, p2, n2]`, dotted at their positive terminals. Being ideal, it has no
+/// magnetizing inductance and passes DC as well.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct IdealTransformer {
+    pub turns_ratio: f64,
+}
+
+impl FromParameters for IdealTransformer {
+    /// The turns ratio can be negative to swap the dots, but has to be finite and not zero.
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        let transformer = Self {
+            turns_ratio: parameters.real("n"),
+        };
 
-        let i_new_re = state.i_old_re + di_dt_re * dt;
-        let i_new_im = state.i_old_im + di_dt_im * dt;
+        if !transformer.turns_ratio.is_finite() || transformer.turns_ratio == 0. {
+            parameters.invalid("n");
+        }
 
-        state.di_per_dt_re = di_dt_re;
-        state.di_per_dt_im = di_dt_im;
-        state.i_old_re = i_new_re;
-        state.i_old_im = i_new_im;
+        transformer
+    }
+}
+
+impl Component for IdealTransformer {
+    type State = ();
+
+    const TERMINAL_COUNT: usize = 5;
+    const BRANCH_COUNT: usize = 1;
+    const PRIORITY: usize = 10;
+    const PARAMETERS: &[&'static str] = &["n", "V1", "V2", "I1", "I2", "P"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[
+        (0, 4),
+        (1, 4),
+        (2, 4),
+        (3, 4),
+        (4, 0),
+        (4, 1),
+        (4, 2),
+        (4, 3),
+    ];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        _: Mode,
+        [p1, n1, p2, n2, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+    ) {
+        let n = c64::real(self.turns_ratio);
+
+        // v1 = n v2, and the primary current i1 is n times the current out of p2
+        net.stamp_voltage_source(p1, Some(n1), k, c64::ZERO);
+        net.add_a(k, p2, -n);
+        net.add_a(k, n2, n);
+        net.add_a(p2, k, -n);
+        net.add_a(n2, k, n);
     }
 
     fn parameter(
         &self,
         net: &LinearEquations,
-        [start, end]: [u32; Self::TERMINAL_COUNT],
-        state: &Self::State,
+        [p1, n1, p2, n2, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
         parameter: &str,
     ) -> Option<c64> {
-        let v = net.get_voltage_across(start, end);
-        let i_prev = c64::new(state.i_old_re, state.i_old_im);
+        let (v1, v2) = (
+            net.get_voltage_across(p1, n1),
+            net.get_voltage_across(p2, n2),
+        );
+        let i1 = net.get_branch_current(k);
+        let i2 = -c64::real(self.turns_ratio) * i1;
 
         match parameter {
-            "L" => Some(c64::new(self.inductance_h, 0.)),
-            "V" => Some(v),
-            "I" => Some(i_prev),
-            "P" => Some(v * i_prev),
+            "n" => Some(c64::real(self.turns_ratio)),
+            "V1" => Some(v1),
+            "V2" => Some(v2),
+            "I1" => Some(i1),
+            "I2" => Some(i2),
+            "P" => Some(v1 * i1 + v2 * i2),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AcSweep, BuildError, CircuitBuilder, ComponentError, ComponentLibrary, DcOperatingPoint,
+        Parser, Probe, Sweep, Timestep, Transient,
+        component::Integration,
+        parser::{build, invalid_parameters},
+    };
+
+    use super::*;
+
+    const MUTUAL: &str = r#"
+        resistor                        in a        R=10
+        inductor                "L1"    a gnd       L=1m
+        inductor                "L2"    out gnd     L=4m
+        mutual-inductance       "K1"    K=0.9 inductor1="L1" inductor2="L2"
+        resistor                        out gnd     R=1G
+        ground                          gnd
+    "#;
+
+    #[test]
+    fn test_coupled_inductors_ac() {
+        let mut circuit = build(&format!("ac-source-1-terminal in V=1 f=0\n{MUTUAL}"));
+
+        // well above the L1/R corner, the open secondary sees the primary times M/L1
+        let response = AcSweep {
+            sweep: Sweep::Linear { points: 1 },
+            start_hz: 100e3,
+            stop_hz: 100e3,
+            probes: vec![Probe::voltage("out"), Probe::voltage("a")],
+        }
+        .run(&mut circuit)
+        .unwrap();
+
+        let out = response.column("V(out)").unwrap()[0];
+        let a = response.column("V(a)").unwrap()[0];
+        assert!((out / a - c64::real(1.8)).norm() < 1e-4, "{:?}", out / a);
+    }
+
+    #[test]
+    fn test_coupled_inductors_transient() {
+        let mut circuit = build(&format!("dc-source-1-terminal in V=1\n{MUTUAL}"));
+
+        let tau = 1e-3 / 10.;
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: tau,
+            step_s: tau / 1000.,
+            timestep: Timestep::Fixed,
+            integration: Integration::Trapezoidal,
+            probes: vec![Probe::voltage("out"), Probe::parameter("L1", "I")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+
+        // the primary current rises as i = (1 - e^(-t/tau)) / R, inducing M di/dt
+        let i1 = waveform.column("I(L1)").unwrap().last().unwrap().re;
+        assert!((i1 - (1. - (-1f64).exp()) / 10.).abs() < 1e-4, "{}", i1);
+
+        let out = waveform.column("V(out)").unwrap().last().unwrap().re;
+        assert!((out - 1.8 * (-1f64).exp()).abs() < 1e-3, "{}", out);
+    }
+
+    #[test]
+    fn test_coupling_errors() {
+        let errors = |netlist: &str| {
+            let mut builder = CircuitBuilder::new();
+            builder.add_commands(Parser::from(netlist).parse_commands().unwrap());
+
+            let Err(errors) = builder.build(&ComponentLibrary::with_builtins()) else {
+                panic!("{netlist} builds");
+            };
+
+            errors
+                .into_iter()
+                .flat_map(|error| match error {
+                    BuildError::Component { errors, .. } => errors,
+                    _ => panic!("unexpected error {:?}", error),
+                })
+                .collect::<Vec<_>>()
+        };
+        let invalid = |errors: &[ComponentError], parameters: &[&str]| {
+            errors.len() == parameters.len()
+                && errors.iter().zip(parameters).all(|(error, expected)| {
+                    matches!(error, ComponentError::InvalidParameter { parameter } if parameter == expected)
+                })
+        };
+
+        let mutual = errors(
+            r#"
+            inductor "L1" a gnd L=1m
+            inductor "L2" b gnd L=0
+            resistor "R1" c gnd R=1k
+            mutual-inductance "K1" K=0.5 inductor1="L1" inductor2="L2"
+            mutual-inductance "K2" K=0.5 inductor1="R1" inductor2="L1"
+            "#,
+        );
+        assert!(invalid(&mutual[..1], &["inductor2"]), "{:?}", mutual);
+        assert!(matches!(
+            &mutual[1..],
+            [ComponentError::UnknownControl { component }] if component == "R1"
+        ));
+
+        let same = errors(r#"mutual-inductance K=-1.5 inductor1="L1" inductor2="L1""#);
+        assert!(invalid(&same, &["inductor2", "K"]), "{:?}", same);
+    }
+
+    #[test]
+    fn test_ideal_transformer() {
+        let mut circuit = build(
+            r#"
+            dc-source-1-terminal            in          V=10
+            transformer             "T1"    in gnd out gnd  n=2
+            resistor                        out gnd     R=5
+            ground                          gnd
+            "#,
+        );
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+
+        assert!((op.voltage("out").unwrap().re - 5.).abs() < 1e-9);
+        assert!((op.parameter("T1", "I1").unwrap().re - 0.5).abs() < 1e-9);
+        assert!((op.parameter("T1", "I2").unwrap().re + 1.).abs() < 1e-9);
+        assert!(op.parameter("T1", "P").unwrap().re.abs() < 1e-9);
+
+        for n in ["0", "1/0"] {
+            assert_eq!(
+                invalid_parameters(&format!("transformer a gnd b gnd n={n}")),
+                ["n"]
+            );
+        }
+    }
+}
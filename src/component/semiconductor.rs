@@ -0,0 +1,861 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    component::{Component, FromParameters, Mode, Parameters},
+    numerical::{LinearEquations, Tolerance, c64},
+};
+
+/// `kT/q` at 300K.
+pub const THERMAL_VOLTAGE: f64 = 25.85e-3;
+
+/// Conductance put across every junction so that reverse-biased ones
+/// don't leave their nodes floating.
+const GMIN: f64 = 1e-12;
+
+/// SPICE's `pnjlim`: limits the step of a junction voltage to `v_new` from `v_old`
+/// to about a thermal voltage per decade of current, so that the exponential
+/// stays in reach of the next Newton–Raphson iteration. `nvt` is the emission
+/// coefficient times the thermal voltage. Returns the limited voltage and whether it was limited.
+pub fn limit_junction(v_new: f64, v_old: f64, nvt: f64, saturation_current: f64) -> (f64, bool) {
+    // where the current curve bends, past it steps are taken in decades
+    let v_critical = nvt * (nvt / (std::f64::consts::SQRT_2 * saturation_current)).ln();
+
+    if v_new <= v_critical || (v_new - v_old).abs() <= 2. * nvt {
+        return (v_new, false);
+    }
+
+    if v_old > 0. {
+        let arg = 1. + (v_new - v_old) / nvt;
+        if arg > 0. {
+            (v_old + nvt * arg.ln(), true)
+        } else {
+            (v_critical, true)
+        }
+    } else {
+        (nvt * (v_new / nvt).ln(), true)
+    }
+}
+
+/// Whether a device current is as close to what its linearization `predicted`
+/// as the Newton–Raphson tolerance asks for.
+fn settled(predicted: f64, current: f64, tolerance: &Tolerance) -> bool {
+    (predicted - current).abs() <= tolerance.current(predicted.abs().max(current.abs()))
+}
+
+/// Reports each of the model `values` that isn't positive as invalid, as the
+/// device equations divide by or take the logarithm of them.
+fn check_positive<const N: usize>(parameters: &mut Parameters, values: [(&str, f64); N]) {
+    for (parameter, value) in values {
+        if value.is_nan() || value <= 0. {
+            parameters.invalid(parameter);
+        }
+    }
+}
+
+/// Current and conductance of a junction `Is (exp(v / nvt) - 1)` at `v`.
+pub fn junction(v: f64, nvt: f64, saturation_current: f64) -> (f64, f64) {
+    let e = (v / nvt).exp();
+    (saturation_current * (e - 1.), saturation_current * e / nvt)
+}
+
+/// Shockley diode from anode to cathode with series resistance and reverse breakdown.
+/// Its current is a branch unknown, so that the series resistance needs no inner node.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Diode {
+    pub saturation_current_ampere: f64,
+    pub emission_coefficient: f64,
+    pub series_resistance_ohm: f64,
+    /// Reverse voltage at which the breakdown current sets in, infinite for none.
+    pub breakdown_volt: f64,
+    /// Reverse current at `breakdown_volt`.
+    pub breakdown_current_ampere: f64,
+}
+
+impl Diode {
+    pub const SILICON: Diode = Diode {
+        saturation_current_ampere: 1e-14,
+        emission_coefficient: 1.,
+        series_resistance_ohm: 0.,
+        breakdown_volt: f64::INFINITY,
+        breakdown_current_ampere: 1e-3,
+    };
+
+    /// About 1.8V at 10mA.
+    pub const RED_LED: Diode = Diode {
+        saturation_current_ampere: 1e-19,
+        emission_coefficient: 1.8,
+        series_resistance_ohm: 2.,
+        breakdown_volt: 5.,
+        breakdown_current_ampere: 1e-5,
+    };
+
+    /// Reads the model parameters, taking those not supplied from `model`.
+    /// `Is`, `N`, `IBV` and `BV` have to be positive, and `Rs` can't be negative.
+    pub fn from_parameters_or(parameters: &mut Parameters, model: Diode) -> Self {
+        let diode = Self {
+            saturation_current_ampere: parameters.real_or("Is", model.saturation_current_ampere),
+            emission_coefficient: parameters.real_or("N", model.emission_coefficient),
+            series_resistance_ohm: parameters.real_or("Rs", model.series_resistance_ohm),
+            breakdown_volt: parameters.real_or("BV", model.breakdown_volt),
+            breakdown_current_ampere: parameters.real_or("IBV", model.breakdown_current_ampere),
+        };
+
+        check_positive(
+            parameters,
+            [
+                ("Is", diode.saturation_current_ampere),
+                ("N", diode.emission_coefficient),
+                ("IBV", diode.breakdown_current_ampere),
+                ("BV", diode.breakdown_volt),
+            ],
+        );
+
+        let resistance_ohm = diode.series_resistance_ohm;
+        if resistance_ohm.is_nan() || resistance_ohm < 0. {
+            parameters.invalid("Rs");
+        }
+
+        diode
+    }
+
+    fn nvt(&self) -> f64 {
+        self.emission_coefficient * THERMAL_VOLTAGE
+    }
+
+    /// Current and conductance at the junction voltage `vd`, breakdown included.
+    fn current(&self, vd: f64) -> (f64, f64) {
+        let nvt = self.nvt();
+        let is = self.saturation_current_ampere;
+
+        let (i_forward, g_forward) = junction(vd, nvt, is);
+        // the breakdown current mirrors the forward one around `-BV`
+        let ibv = self.breakdown_current_ampere;
+        let (i_breakdown, g_breakdown) = junction(-vd - self.breakdown_volt, nvt, ibv);
+
+        (
+            i_forward - i_breakdown - ibv + GMIN * vd,
+            g_forward + g_breakdown + GMIN,
+        )
+    }
+}
+
+impl FromParameters for Diode {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self::from_parameters_or(parameters, Diode::SILICON)
+    }
+}
+
+/// The junction voltage the diode is linearized around.
+#[derive(Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct DiodeState {
+    vd: f64,
+}
+
+impl Component for Diode {
+    type State = DiodeState;
+
+    const TERMINAL_COUNT: usize = 3;
+    const BRANCH_COUNT: usize = 1;
+    const PRIORITY: usize = 10;
+    const PARAMETERS: &[&'static str] = &["Is", "N", "Rs", "BV", "IBV", "V", "Vd", "I", "P"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 2), (1, 2), (2, 0), (2, 1), (2, 2)];
+    const NONLINEAR: bool = true;
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        [a, c, k]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        let (i, g) = self.current(state.vd);
+        let i_eq = match mode {
+            Mode::Ac { .. } => 0.,
+            Mode::Dc { .. } | Mode::Transient { .. } | Mode::Initial => i - g * state.vd,
+        };
+
+        // i = g (v(a) - v(c) - Rs i) + i_eq
+        net.add_a(a, k, c64::ONE);
+        net.add_a(c, k, -c64::ONE);
+        net.add_a(k, k, c64::real(1. + g * self.series_resistance_ohm));
+        net.add_a(k, a, c64::real(-g));
+        net.add_a(k, c, c64::real(g));
+        net.add_b(k, c64::real(i_eq));
+    }
+
+    fn limit(
+        &self,
+        net: &LinearEquations,
+        _mode: Mode,
+        [a, c, k]: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+        tolerance: &Tolerance,
+    ) -> bool {
+        let vd = (net.get_voltage_across(a, c)
+            - c64::real(self.series_resistance_ohm) * net.get_branch_current(k))
+        .re;
+
+        let (i_old, g_old) = self.current(state.vd);
+        let predicted = i_old + g_old * (vd - state.vd);
+        let unsettled = !settled(predicted, self.current(vd).0, tolerance);
+
+        let nvt = self.nvt();
+        let (vd, limited) = if vd < -self.breakdown_volt {
+            let (vr, limited) = limit_junction(
+                -vd - self.breakdown_volt,
+                -state.vd - self.breakdown_volt,
+                nvt,
+                self.breakdown_current_ampere,
+            );
+            (-vr - self.breakdown_volt, limited)
+        } else {
+            limit_junction(vd, state.vd, nvt, self.saturation_current_ampere)
+        };
+
+        state.vd = vd;
+        limited || unsettled
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [a, c, k]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = net.get_voltage_across(a, c);
+        let i = net.get_branch_current(k);
+
+        match parameter {
+            "Is" => Some(c64::real(self.saturation_current_ampere)),
+            "N" => Some(c64::real(self.emission_coefficient)),
+            "Rs" => Some(c64::real(self.series_resistance_ohm)),
+            "BV" => Some(c64::real(self.breakdown_volt)),
+            "IBV" => Some(c64::real(self.breakdown_current_ampere)),
+            "V" => Some(v),
+            "Vd" => Some(c64::real(state.vd)),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            _ => None,
+        }
+    }
+}
+
+/// Bipolar transistor between collector, base and emitter, the Gummel–Poon transport
+/// model without high-injection and leakage effects. A PNP is an NPN with every
+/// voltage and current flipped.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Bjt {
+    /// 1 for an NPN, -1 for a PNP.
+    pub polarity: f64,
+    pub saturation_current_ampere: f64,
+    pub forward_beta: f64,
+    pub reverse_beta: f64,
+    /// Forward Early voltage, infinite for none.
+    pub early_volt: f64,
+}
+
+impl Bjt {
+    pub const NPN: Bjt = Bjt {
+        polarity: 1.,
+        saturation_current_ampere: 1e-16,
+        forward_beta: 100.,
+        reverse_beta: 1.,
+        early_volt: f64::INFINITY,
+    };
+
+    pub const PNP: Bjt = Bjt {
+        polarity: -1.,
+        ..Bjt::NPN
+    };
+
+    /// Reads the model parameters, taking those not supplied from `model`.
+    /// `Is`, `Bf` and `Br` have to be positive.
+    pub fn from_parameters_or(parameters: &mut Parameters, model: Bjt) -> Self {
+        let bjt = Self {
+            polarity: model.polarity,
+            saturation_current_ampere: parameters.real_or("Is", model.saturation_current_ampere),
+            forward_beta: parameters.real_or("Bf", model.forward_beta),
+            reverse_beta: parameters.real_or("Br", model.reverse_beta),
+            early_volt: parameters.real_or("Vaf", model.early_volt),
+        };
+
+        check_positive(
+            parameters,
+            [
+                ("Is", bjt.saturation_current_ampere),
+                ("Bf", bjt.forward_beta),
+                ("Br", bjt.reverse_beta),
+            ],
+        );
+
+        bjt
+    }
+
+    /// Collector and base currents of an NPN at the junction voltages,
+    /// each with its derivatives by `vbe` and `vbc`.
+    fn currents(&self, vbe: f64, vbc: f64) -> [(f64, f64, f64); 2] {
+        let is = self.saturation_current_ampere;
+
+        let (i_f, g_f) = junction(vbe, THERMAL_VOLTAGE, is);
+        let (i_r, g_r) = junction(vbc, THERMAL_VOLTAGE, is);
+        let (i_f, g_f) = (i_f + GMIN * vbe, g_f + GMIN);
+        let (i_r, g_r) = (i_r + GMIN * vbc, g_r + GMIN);
+
+        // the Early effect widens the transport current with the reverse bias of the collector
+        let early = 1. - vbc / self.early_volt;
+        let i_t = (i_f - i_r) * early;
+
+        let i_c = i_t - i_r / self.reverse_beta;
+        let i_b = i_f / self.forward_beta + i_r / self.reverse_beta;
+
+        [
+            (
+                i_c,
+                g_f * early,
+                -g_r * early - (i_f - i_r) / self.early_volt - g_r / self.reverse_beta,
+            ),
+            (i_b, g_f / self.forward_beta, g_r / self.reverse_beta),
+        ]
+    }
+
+    /// `vbe` and `vbc` of the equivalent NPN.
+    fn junction_voltages(&self, net: &LinearEquations, [c, b, e]: [u32; 3]) -> (f64, f64) {
+        (
+            self.polarity * net.get_voltage_across(b, e).re,
+            self.polarity * net.get_voltage_across(b, c).re,
+        )
+    }
+}
+
+/// The junction voltages of the equivalent NPN the transistor is linearized around.
+#[derive(Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct BjtState {
+    vbe: f64,
+    vbc: f64,
+}
+
+impl Component for Bjt {
+    type State = BjtState;
+
+    const TERMINAL_COUNT: usize = 3;
+    const PRIORITY: usize = 10;
+    const PARAMETERS: &[&'static str] = &["Is", "Bf", "Br", "Vaf", "Ic", "Ib", "Vbe", "Vce", "P"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (1, 0),
+        (1, 1),
+        (1, 2),
+        (2, 0),
+        (2, 1),
+        (2, 2),
+    ];
+    const NONLINEAR: bool = true;
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        [c, b, e]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        let BjtState { vbe, vbc } = *state;
+        let [(i_c, gc_be, gc_bc), (i_b, gb_be, gb_bc)] = self.currents(vbe, vbc);
+
+        // the currents drawn into each terminal, their conductances to
+        // the collector, base and emitter voltages and equivalent sources
+        let terminal = |i: f64, g_be: f64, g_bc: f64| {
+            let i_eq = match mode {
+                Mode::Ac { .. } => 0.,
+                Mode::Dc { .. } | Mode::Transient { .. } | Mode::Initial => {
+                    self.polarity * (i - g_be * vbe - g_bc * vbc)
+                }
+            };
+            ([-g_bc, g_be + g_bc, -g_be], i_eq)
+        };
+
+        let (g_c, i_c) = terminal(i_c, gc_be, gc_bc);
+        let (g_b, i_b) = terminal(i_b, gb_be, gb_bc);
+        let g_e = [0, 1, 2].map(|j| -g_c[j] - g_b[j]);
+        let i_e = -i_c - i_b;
+
+        for (row, g, i_eq) in [(c, g_c, i_c), (b, g_b, i_b), (e, g_e, i_e)] {
+            for (column, g) in [c, b, e].into_iter().zip(g) {
+                net.add_a(row, column, c64::real(g));
+            }
+            net.add_b(row, c64::real(-i_eq));
+        }
+    }
+
+    fn limit(
+        &self,
+        net: &LinearEquations,
+        _mode: Mode,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+        tolerance: &Tolerance,
+    ) -> bool {
+        let (vbe, vbc) = self.junction_voltages(net, terminals);
+
+        let (dvbe, dvbc) = (vbe - state.vbe, vbc - state.vbc);
+        let unsettled = self
+            .currents(state.vbe, state.vbc)
+            .into_iter()
+            .zip(self.currents(vbe, vbc))
+            .any(|((i_old, g_be, g_bc), (i, ..))| {
+                !settled(i_old + g_be * dvbe + g_bc * dvbc, i, tolerance)
+            });
+
+        let is = self.saturation_current_ampere;
+        let (vbe, limited_be) = limit_junction(vbe, state.vbe, THERMAL_VOLTAGE, is);
+        let (vbc, limited_bc) = limit_junction(vbc, state.vbc, THERMAL_VOLTAGE, is);
+
+        *state = BjtState { vbe, vbc };
+        limited_be || limited_bc || unsettled
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let (vbe, vbc) = self.junction_voltages(net, terminals);
+        let [(i_c, ..), (i_b, ..)] = self.currents(vbe, vbc);
+        let p = self.polarity;
+
+        match parameter {
+            "Is" => Some(c64::real(self.saturation_current_ampere)),
+            "Bf" => Some(c64::real(self.forward_beta)),
+            "Br" => Some(c64::real(self.reverse_beta)),
+            "Vaf" => Some(c64::real(self.early_volt)),
+            "Ic" => Some(c64::real(p * i_c)),
+            "Ib" => Some(c64::real(p * i_b)),
+            "Vbe" => Some(c64::real(p * vbe)),
+            "Vce" => Some(c64::real(p * (vbe - vbc))),
+            "P" => Some(c64::real((vbe - vbc) * i_c + vbe * i_b)),
+            _ => None,
+        }
+    }
+}
+
+/// Level 1 (Shichman–Hodges) MOSFET between drain, gate and source, with the body
+/// tied to the source. The drain and source swap roles when `Vds` reverses. A PMOS is
+/// an NMOS with every voltage and current flipped, its `Vto` is negative as in SPICE.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Mosfet {
+    /// 1 for an N-channel, -1 for a P-channel device.
+    pub polarity: f64,
+    pub threshold_volt: f64,
+    /// Process transconductance `µ Cox`, in A/V².
+    pub transconductance: f64,
+    /// Channel-length modulation, in 1/V.
+    pub lambda: f64,
+    pub width_m: f64,
+    pub length_m: f64,
+}
+
+/// Where a MOSFET operates, reported as the `region` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MosfetRegion {
+    Cutoff = 0,
+    Linear = 1,
+    Saturation = 2,
+}
+
+impl Mosfet {
+    pub const NMOS: Mosfet = Mosfet {
+        polarity: 1.,
+        threshold_volt: 0.7,
+        transconductance: 2e-5,
+        lambda: 0.,
+        width_m: 10e-6,
+        length_m: 1e-6,
+    };
+
+    pub const PMOS: Mosfet = Mosfet {
+        polarity: -1.,
+        threshold_volt: -0.7,
+        ..Mosfet::NMOS
+    };
+
+    /// Reads the model parameters, taking those not supplied from `model`.
+    /// `Kp`, `W` and `L` have to be positive.
+    pub fn from_parameters_or(parameters: &mut Parameters, model: Mosfet) -> Self {
+        let mosfet = Self {
+            polarity: model.polarity,
+            threshold_volt: parameters.real_or("Vto", model.threshold_volt),
+            transconductance: parameters.real_or("Kp", model.transconductance),
+            lambda: parameters.real_or("lambda", model.lambda),
+            width_m: parameters.real_or("W", model.width_m),
+            length_m: parameters.real_or("L", model.length_m),
+        };
+
+        check_positive(
+            parameters,
+            [
+                ("Kp", mosfet.transconductance),
+                ("W", mosfet.width_m),
+                ("L", mosfet.length_m),
+            ],
+        );
+
+        mosfet
+    }
+
+    /// Drain current of the equivalent NMOS with `Vds >= 0`,
+    /// its transconductance `gm`, output conductance `gds` and region.
+    fn forward(&self, vgs: f64, vds: f64) -> (f64, f64, f64, MosfetRegion) {
+        let beta = self.transconductance * self.width_m / self.length_m;
+        let overdrive = vgs - self.polarity * self.threshold_volt;
+        let modulation = 1. + self.lambda * vds;
+
+        if overdrive <= 0. {
+            (0., 0., 0., MosfetRegion::Cutoff)
+        } else if vds < overdrive {
+            let i = beta * (overdrive * vds - vds * vds / 2.);
+            (
+                i * modulation,
+                beta * vds * modulation,
+                beta * (overdrive - vds) * modulation + i * self.lambda,
+                MosfetRegion::Linear,
+            )
+        } else {
+            let i = beta / 2. * overdrive * overdrive;
+            (
+                i * modulation,
+                beta * overdrive * modulation,
+                i * self.lambda,
+                MosfetRegion::Saturation,
+            )
+        }
+    }
+
+    /// Drain current of the equivalent NMOS with its derivatives by `vgs` and `vds`,
+    /// swapping drain and source for a negative `vds`.
+    fn drain_current(&self, vgs: f64, vds: f64) -> (f64, f64, f64, MosfetRegion) {
+        let (i, gm, gds, region) = if vds >= 0. {
+            self.forward(vgs, vds)
+        } else {
+            let (i, gm, gds, region) = self.forward(vgs - vds, -vds);
+            (-i, -gm, gm + gds, region)
+        };
+
+        (i + GMIN * vds, gm, gds + GMIN, region)
+    }
+
+    /// `vgs` and `vds` of the equivalent NMOS.
+    fn terminal_voltages(&self, net: &LinearEquations, [d, g, s]: [u32; 3]) -> (f64, f64) {
+        (
+            self.polarity * net.get_voltage_across(g, s).re,
+            self.polarity * net.get_voltage_across(d, s).re,
+        )
+    }
+}
+
+/// The voltages of the equivalent NMOS the transistor is linearized around.
+#[derive(Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct MosfetState {
+    vgs: f64,
+    vds: f64,
+}
+
+impl Component for Mosfet {
+    type State = MosfetState;
+
+    const TERMINAL_COUNT: usize = 3;
+    const PRIORITY: usize = 10;
+    const PARAMETERS: &[&'static str] = &[
+        "Vto", "Kp", "lambda", "W", "L", "Id", "Vgs", "Vds", "gm", "gds", "region", "P",
+    ];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (0, 1), (0, 2), (2, 0), (2, 1), (2, 2)];
+    const NONLINEAR: bool = true;
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        [d, g, s]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        let MosfetState { vgs, vds } = *state;
+        let (i, gm, gds, _) = self.drain_current(vgs, vds);
+
+        let i_eq = match mode {
+            Mode::Ac { .. } => 0.,
+            Mode::Dc { .. } | Mode::Transient { .. } | Mode::Initial => {
+                self.polarity * (i - gm * vgs - gds * vds)
+            }
+        };
+
+        // the drain current leaves through the source, the gate draws none
+        let conductances = [(d, gds), (g, gm), (s, -gm - gds)];
+        for (row, sign) in [(d, 1.), (s, -1.)] {
+            for (column, g) in conductances {
+                net.add_a(row, column, c64::real(sign * g));
+            }
+            net.add_b(row, c64::real(-sign * i_eq));
+        }
+    }
+
+    fn limit(
+        &self,
+        net: &LinearEquations,
+        _mode: Mode,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+        tolerance: &Tolerance,
+    ) -> bool {
+        let (vgs, vds) = self.terminal_voltages(net, terminals);
+
+        let (i_old, gm, gds, _) = self.drain_current(state.vgs, state.vds);
+        let predicted = i_old + gm * (vgs - state.vgs) + gds * (vds - state.vds);
+        let (i, ..) = self.drain_current(vgs, vds);
+
+        *state = MosfetState { vgs, vds };
+        !settled(predicted, i, tolerance)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let (vgs, vds) = self.terminal_voltages(net, terminals);
+        let (i, gm, gds, region) = self.drain_current(vgs, vds);
+        let p = self.polarity;
+
+        match parameter {
+            "Vto" => Some(c64::real(self.threshold_volt)),
+            "Kp" => Some(c64::real(self.transconductance)),
+            "lambda" => Some(c64::real(self.lambda)),
+            "W" => Some(c64::real(self.width_m)),
+            "L" => Some(c64::real(self.length_m)),
+            "Id" => Some(c64::real(p * i)),
+            "Vgs" => Some(c64::real(p * vgs)),
+            "Vds" => Some(c64::real(p * vds)),
+            "gm" => Some(c64::real(gm)),
+            "gds" => Some(c64::real(gds)),
+            "region" => Some(c64::real(region as u8 as f64)),
+            "P" => Some(c64::real(vds * i)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DcOperatingPoint,
+        circuit::Circuit,
+        component::{DC1Source, DCCurrentSource, Ground, Resistor},
+        parser::{build, invalid_parameters},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_forward_biased_diode() {
+        let mut circuit = Circuit::new();
+        let [vin, out, gnd] = ["in", "out", "gnd"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 5. }, None, [vin]);
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            None,
+            [vin, out],
+        );
+        circuit.put(Diode::SILICON, Some("D1".into()), [out, gnd]);
+        circuit.put(Ground, None, [gnd]);
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+
+        let v = op.voltage("out").unwrap().re;
+        let i = op.parameter("D1", "I").unwrap().re;
+
+        assert!(v > 0.6 && v < 0.75, "{}", v);
+        assert!((i - (5. - v) / 1e3).abs() < 1e-9);
+        assert!((i - 1e-14 * ((v / THERMAL_VOLTAGE).exp() - 1.)).abs() < 1e-3 * i);
+    }
+
+    #[test]
+    fn test_zener_and_led() {
+        let mut circuit = build(
+            "
+            dc-source-1-terminal in V=12
+            resistor in z R=1k
+            zener \"Z1\" gnd z BV=5.1
+            resistor in led R=330
+            led \"D1\" led gnd
+            ground gnd
+            ",
+        );
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+
+        let v_zener = op.voltage("z").unwrap().re;
+        assert!(v_zener > 5.1 && v_zener < 5.2, "{}", v_zener);
+
+        let v_led = op.voltage("led").unwrap().re;
+        assert!(v_led > 1.7 && v_led < 2., "{}", v_led);
+        assert!((op.parameter("D1", "I").unwrap().re - (12. - v_led) / 330.).abs() < 1e-9);
+
+        assert_eq!(
+            invalid_parameters("diode a b Is=0 N=-1 IBV=0 Rs=-1"),
+            ["Is", "N", "IBV", "Rs"]
+        );
+        // the breakdown would set in forward as well
+        for bv in ["0", "-5"] {
+            assert_eq!(invalid_parameters(&format!("zener a b BV={bv}")), ["BV"]);
+        }
+        assert_eq!(invalid_parameters("zener a b BV=5.1 N=0"), ["N"]);
+    }
+
+    #[test]
+    fn test_limit_junction() {
+        // a huge step is cut down to a handful of thermal voltages
+        let (v, limited) = limit_junction(5., 0.6, THERMAL_VOLTAGE, 1e-14);
+        assert!(limited);
+        assert!(v > 0.6 && v < 0.8, "{}", v);
+
+        let (v, limited) = limit_junction(0.65, 0.64, THERMAL_VOLTAGE, 1e-14);
+        assert!(!limited);
+        assert_eq!(v, 0.65);
+    }
+
+    #[test]
+    fn test_bjt_forward_active() {
+        let mut circuit = Circuit::new();
+        let [vcc, c, b, gnd] = ["vcc", "c", "b", "gnd"].map(|net| circuit.net(net));
+
+        circuit.put(DC1Source { voltage_volt: 10. }, None, [vcc]);
+        circuit.put(Ground, None, [gnd]);
+        circuit.put(
+            DCCurrentSource {
+                current_ampere: 10e-6,
+            },
+            None,
+            [b, gnd],
+        );
+        circuit.put(
+            Resistor {
+                resistance_ohm: 1e3,
+            },
+            None,
+            [vcc, c],
+        );
+        circuit.put(Bjt::NPN, Some("Q1".into()), [c, b, gnd]);
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+
+        let i_c = op.parameter("Q1", "Ic").unwrap().re;
+        assert!((i_c - 1e-3).abs() < 1e-6, "{}", i_c);
+        assert!((op.parameter("Q1", "Ib").unwrap().re - 10e-6).abs() < 1e-9);
+        assert!((op.voltage("c").unwrap().re - 9.).abs() < 1e-3);
+
+        let vbe = op.parameter("Q1", "Vbe").unwrap().re;
+        assert!(vbe > 0.6 && vbe < 0.8, "{}", vbe);
+    }
+
+    #[test]
+    fn test_pnp_with_early_effect() {
+        let mut circuit = build(
+            "
+            dc-source-1-terminal vcc V=10
+            dc-current-source gnd b I=10u
+            pnp \"Q1\" c b vcc Vaf=50
+            resistor c gnd R=1k
+            ground gnd
+            ",
+        );
+
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+
+        let i_c = op.parameter("Q1", "Ic").unwrap().re;
+        let vce = op.parameter("Q1", "Vce").unwrap().re;
+        let vbe = op.parameter("Q1", "Vbe").unwrap().re;
+
+        assert!(vce < -8. && vbe < -0.6, "{} {}", vce, vbe);
+        // the transport current grows by the collector-base reverse bias over Vaf
+        let expected = -1e-3 * (1. + (vbe - vce) / 50.);
+        assert!((i_c - expected).abs() < 1e-3 * expected.abs(), "{}", i_c);
+        assert!((op.voltage("c").unwrap().re + 1e3 * i_c).abs() < 1e-3);
+
+        assert_eq!(
+            invalid_parameters("pnp c b e Is=-1f Bf=0 Br=-1"),
+            ["Is", "Bf", "Br"]
+        );
+    }
+
+    #[test]
+    fn test_nmos_regions() {
+        let netlist = |rd: &str| {
+            format!(
+                "
+                dc-source-1-terminal vdd V=5
+                dc-source-1-terminal g V=2
+                resistor vdd d R={}
+                nmos \"M1\" d g gnd Kp=1m W=1u L=1u
+                ground gnd
+                ",
+                rd
+            )
+        };
+
+        let mut circuit = build(&netlist("1k"));
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+
+        // 1mA/V² / 2 · (2V - 0.7V)²
+        let i_d = op.parameter("M1", "Id").unwrap().re;
+        assert!((i_d - 0.845e-3).abs() < 1e-9, "{}", i_d);
+        assert_eq!(op.parameter("M1", "region").unwrap().re, 2.);
+        assert!((op.parameter("M1", "gm").unwrap().re - 1.3e-3).abs() < 1e-9);
+
+        let mut circuit = build(&netlist("10k"));
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+
+        let i_d = op.parameter("M1", "Id").unwrap().re;
+        let vds = op.parameter("M1", "Vds").unwrap().re;
+        assert_eq!(op.parameter("M1", "region").unwrap().re, 1.);
+        assert!((i_d - 1e-3 * (1.3 * vds - vds * vds / 2.)).abs() < 1e-9);
+        assert!((i_d - (5. - vds) / 10e3).abs() < 1e-6, "{} {}", i_d, vds);
+
+        assert_eq!(
+            invalid_parameters("nmos d g s Kp=0 W=-1u L=0"),
+            ["Kp", "W", "L"]
+        );
+    }
+
+    #[test]
+    fn test_cmos_inverter() {
+        for (input, output) in [(0., 5.), (5., 0.)] {
+            let mut circuit = build(&format!(
+                "
+                dc-source-1-terminal vdd V=5
+                dc-source-1-terminal in V={}
+                pmos \"MP\" out in vdd
+                nmos \"MN\" out in gnd
+                ground gnd
+                ",
+                input
+            ));
+
+            let op = DcOperatingPoint.run(&mut circuit).unwrap();
+            let v = op.voltage("out").unwrap().re;
+            assert!((v - output).abs() < 1e-3, "{} -> {}", input, v);
+        }
+    }
+}
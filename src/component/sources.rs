@@ -3,56 +3,751 @@ use std::f64::consts::PI;
 use bytemuck::{Pod, Zeroable};
 
 use crate::{
-    component::Component,
+    buffer::{Table, Tables},
+    component::{Component, FromParameters, Mode, Parameters, reached},
     numerical::{LinearEquations, c64},
 };
 
+/// DC stimulus, which small-signal analysis sees as zero and source stepping scales.
+fn dc(value: f64, mode: Mode) -> c64 {
+    match mode {
+        Mode::Dc { source_scale } => c64::new(value * source_scale, 0.),
+        Mode::Transient { .. } | Mode::Initial => c64::new(value, 0.),
+        Mode::Ac { .. } => c64::ZERO,
+    }
+}
+
+/// Sinusoidal stimulus at the end of the step starting at `t`,
+/// or its phasor in small-signal analysis. It has no DC component.
+fn sinusoid(amplitude: f64, frequency_hz: f64, phase_rad: f64, t: f64, mode: Mode) -> c64 {
+    match mode {
+        Mode::Transient { dt, .. } => {
+            c64::polar(amplitude, 2.0 * PI * frequency_hz * (t + dt) + phase_rad)
+        }
+        Mode::Initial => c64::polar(amplitude, 2.0 * PI * frequency_hz * t + phase_rad),
+        Mode::Ac { .. } => c64::polar(amplitude, phase_rad),
+        Mode::Dc { .. } => c64::ZERO,
+    }
+}
+
+/// How far into the step starting at `t` the `corner` is, see [`Component::breakpoint`].
+fn until_corner(corner: Option<f64>, t: f64, mode: Mode) -> Option<f64> {
+    let Mode::Transient { dt, .. } = mode else {
+        return None;
+    };
+
+    let until = corner? - t;
+    (until < dt).then_some(until)
+}
+
+/// The time a [`Stimulus`] is at, kept in the state of the source it drives.
+pub trait Clock: Pod + Default {
+    fn time(&self) -> f64;
+
+    fn time_mut(&mut self) -> &mut f64;
+
+    /// Moves on to the end of the step just accepted.
+    fn advance(&mut self, mode: Mode) {
+        if let Mode::Transient { dt, .. } = mode {
+            *self.time_mut() += dt;
+        }
+    }
+}
+
+impl Clock for f64 {
+    fn time(&self) -> f64 {
+        *self
+    }
+
+    fn time_mut(&mut self) -> &mut f64 {
+        self
+    }
+}
+
+/// A waveform over time alone, which [`Stimulus1Source`], [`Stimulus2Source`] and
+/// [`StimulusCurrentSource`] drive their output with.
+pub trait Stimulus: Pod {
+    type State: Clock;
+    /// Everything a voltage source driven by it reports, `V`, `I` and `P` first.
+    const VOLTAGE_PARAMETERS: &[&'static str];
+    /// Everything a current source driven by it reports, `I`, `V` and `P` first.
+    /// Empty for those only registered as voltage sources.
+    const CURRENT_PARAMETERS: &[&'static str] = &[];
+
+    /// Its value at `t`, which is at most a step away from where `state` is.
+    fn value_at(&self, state: &Self::State, t: f64) -> f64;
+
+    /// Its value at the DC operating point, by default where `state` is.
+    fn dc_value(&self, state: &Self::State) -> f64 {
+        self.value_at(state, state.time())
+    }
+
+    /// The first corner after where `state` is, if it has any.
+    fn corner_after(&self, _state: &Self::State) -> Option<f64> {
+        None
+    }
+
+    /// See [`Component::load`].
+    fn load(&self, _tables: &Tables, _state: &mut Self::State) {}
+
+    /// Its settings by their netlist names, with any levels named after `quantity`.
+    fn parameter(&self, parameter: &str, quantity: &str) -> Option<c64>;
+
+    /// The stimulus at the end of the step starting where `state` is, with no small-signal part.
+    fn at(&self, state: &Self::State, mode: Mode) -> c64 {
+        match mode {
+            Mode::Transient { dt, .. } => c64::real(self.value_at(state, state.time() + dt)),
+            Mode::Initial => c64::real(self.value_at(state, state.time())),
+            Mode::Dc { .. } => dc(self.dc_value(state), mode),
+            Mode::Ac { .. } => c64::ZERO,
+        }
+    }
+
+    /// How far into the step starting where `state` is the next corner is, see [`Component::breakpoint`].
+    fn breakpoint(&self, state: &Self::State, mode: Mode) -> Option<f64> {
+        until_corner(self.corner_after(state), state.time(), mode)
+    }
+}
+
+/// SPICE's `PULSE`: sits at `initial` for `delay_s`, then ramps to `pulsed` over `rise_s`,
+/// stays there for `width_s` and ramps back over `fall_s`, repeating every `period_s`.
+/// An infinite width or period makes it a single step or pulse.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Pulse {
+    pub initial: f64,
+    pub pulsed: f64,
+    pub delay_s: f64,
+    pub rise_s: f64,
+    pub fall_s: f64,
+    pub width_s: f64,
+    pub period_s: f64,
+}
+
+impl Pulse {
+    /// Reads the levels as `{quantity}1` and `{quantity}2`, then `TD`, `TR`, `TF`, `PW` and `PER`.
+    /// None of the times can be negative, and the period has to fit the pulse.
+    pub fn from_parameters(parameters: &mut Parameters, quantity: &str) -> Self {
+        let pulse = Self {
+            initial: parameters.real(&format!("{quantity}1")),
+            pulsed: parameters.real(&format!("{quantity}2")),
+            delay_s: parameters.real_or("TD", 0.),
+            rise_s: parameters.real_or("TR", 0.),
+            fall_s: parameters.real_or("TF", 0.),
+            width_s: parameters.real_or("PW", f64::INFINITY),
+            period_s: parameters.real_or("PER", f64::INFINITY),
+        };
+
+        for (parameter, time_s) in [
+            ("TD", pulse.delay_s),
+            ("TR", pulse.rise_s),
+            ("TF", pulse.fall_s),
+            ("PW", pulse.width_s),
+        ] {
+            if time_s.is_nan() || time_s < 0. {
+                parameters.invalid(parameter);
+            }
+        }
+
+        let pulse_s = pulse.rise_s + pulse.width_s + pulse.fall_s;
+        if pulse.period_s.is_nan() || pulse.period_s <= 0. || pulse.period_s < pulse_s {
+            parameters.invalid("PER");
+        }
+
+        pulse
+    }
+
+    pub fn value(&self, t: f64) -> f64 {
+        if t < self.delay_s {
+            return self.initial;
+        }
+
+        let mut tau = t - self.delay_s;
+        if self.period_s.is_finite() {
+            tau %= self.period_s;
+        }
+
+        let falling = self.rise_s + self.width_s;
+        let swing = self.pulsed - self.initial;
+
+        if tau < self.rise_s {
+            self.initial + swing * tau / self.rise_s
+        } else if tau < falling {
+            self.pulsed
+        } else if tau < falling + self.fall_s {
+            self.pulsed - swing * (tau - falling) / self.fall_s
+        } else {
+            self.initial
+        }
+    }
+
+    /// The first corner of the waveform after `t`.
+    pub fn next_corner(&self, t: f64) -> Option<f64> {
+        let offsets = [
+            0.,
+            self.rise_s,
+            self.rise_s + self.width_s,
+            self.rise_s + self.width_s + self.fall_s,
+        ];
+
+        let cycle = if self.period_s.is_finite() && t > self.delay_s {
+            ((t - self.delay_s) / self.period_s).floor()
+        } else {
+            0.
+        };
+
+        [cycle, cycle + 1.]
+            .into_iter()
+            .filter(|&cycle| cycle == 0. || self.period_s.is_finite())
+            .flat_map(|cycle| {
+                let start = self.delay_s
+                    + if cycle == 0. {
+                        0.
+                    } else {
+                        cycle * self.period_s
+                    };
+                offsets.map(|offset| start + offset)
+            })
+            .filter(|&corner| corner.is_finite() && !reached(corner, t))
+            .reduce(f64::min)
+    }
+}
+
+impl Stimulus for Pulse {
+    type State = f64;
+    const VOLTAGE_PARAMETERS: &[&'static str] = &[
+        "V", "I", "P", "V1", "V2", "TD", "TR", "TF", "PW", "PER", "t",
+    ];
+    const CURRENT_PARAMETERS: &[&'static str] = &[
+        "I", "V", "P", "I1", "I2", "TD", "TR", "TF", "PW", "PER", "t",
+    ];
+
+    fn value_at(&self, _: &f64, t: f64) -> f64 {
+        self.value(t)
+    }
+
+    /// At DC it is at its initial level.
+    fn dc_value(&self, _: &f64) -> f64 {
+        self.initial
+    }
+
+    fn corner_after(&self, &t: &f64) -> Option<f64> {
+        self.next_corner(t)
+    }
+
+    /// Its settings, with the levels as `{quantity}1` and `{quantity}2`.
+    fn parameter(&self, parameter: &str, quantity: &str) -> Option<c64> {
+        let value = match parameter.strip_prefix(quantity) {
+            Some("1") => self.initial,
+            Some("2") => self.pulsed,
+            _ => match parameter {
+                "TD" => self.delay_s,
+                "TR" => self.rise_s,
+                "TF" => self.fall_s,
+                "PW" => self.width_s,
+                "PER" => self.period_s,
+                _ => return None,
+            },
+        };
+
+        Some(c64::real(value))
+    }
+}
+
+/// SPICE's `EXP`: sits at `initial` until `rise_delay_s`, then approaches `pulsed` with
+/// the time constant `rise_tau_s`, and from `fall_delay_s` on heads back with `fall_tau_s`.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Exponential {
+    pub initial: f64,
+    pub pulsed: f64,
+    pub rise_delay_s: f64,
+    pub rise_tau_s: f64,
+    pub fall_delay_s: f64,
+    pub fall_tau_s: f64,
+}
+
+impl Exponential {
+    /// Reads the levels as `V1` and `V2`, then `TD1`, `TAU1`, `TD2` and `TAU2`.
+    /// Without `TD2` it never falls, and `TAU2` defaults to `TAU1`. The delays can't be
+    /// negative, nor can it fall before it rises, and the time constants have to be
+    /// positive, or it wouldn't settle.
+    pub fn from_parameters(parameters: &mut Parameters) -> Self {
+        let rise_tau_s = parameters.real("TAU1");
+
+        let exponential = Self {
+            initial: parameters.real("V1"),
+            pulsed: parameters.real("V2"),
+            rise_delay_s: parameters.real_or("TD1", 0.),
+            rise_tau_s,
+            fall_delay_s: parameters.real_or("TD2", f64::INFINITY),
+            fall_tau_s: parameters.real_or("TAU2", rise_tau_s),
+        };
+
+        for (parameter, delay_s) in [
+            ("TD1", exponential.rise_delay_s),
+            ("TD2", exponential.fall_delay_s),
+        ] {
+            if delay_s.is_nan() || delay_s < 0. {
+                parameters.invalid(parameter);
+            }
+        }
+
+        if exponential.fall_delay_s < exponential.rise_delay_s {
+            parameters.invalid("TD2");
+        }
+
+        for (parameter, tau_s) in [
+            ("TAU1", exponential.rise_tau_s),
+            ("TAU2", exponential.fall_tau_s),
+        ] {
+            if tau_s.is_nan() || tau_s <= 0. {
+                parameters.invalid(parameter);
+            }
+        }
+
+        exponential
+    }
+
+    pub fn value(&self, t: f64) -> f64 {
+        let swing = self.pulsed - self.initial;
+        let approach = |delay_s: f64, tau_s: f64| {
+            if t > delay_s {
+                1. - (-(t - delay_s) / tau_s).exp()
+            } else {
+                0.
+            }
+        };
+
+        self.initial + swing * approach(self.rise_delay_s, self.rise_tau_s)
+            - swing * approach(self.fall_delay_s, self.fall_tau_s)
+    }
+
+    /// The first of the delays after `t`, where the waveform has a kink.
+    pub fn next_corner(&self, t: f64) -> Option<f64> {
+        [self.rise_delay_s, self.fall_delay_s]
+            .into_iter()
+            .find(|&corner| corner.is_finite() && !reached(corner, t))
+    }
+}
+
+impl Stimulus for Exponential {
+    type State = f64;
+    const VOLTAGE_PARAMETERS: &[&'static str] =
+        &["V", "I", "P", "V1", "V2", "TD1", "TAU1", "TD2", "TAU2", "t"];
+
+    fn value_at(&self, _: &f64, t: f64) -> f64 {
+        self.value(t)
+    }
+
+    fn corner_after(&self, &t: &f64) -> Option<f64> {
+        self.next_corner(t)
+    }
+
+    fn parameter(&self, parameter: &str, _: &str) -> Option<c64> {
+        let value = match parameter {
+            "V1" => self.initial,
+            "V2" => self.pulsed,
+            "TD1" => self.rise_delay_s,
+            "TAU1" => self.rise_tau_s,
+            "TD2" => self.fall_delay_s,
+            "TAU2" => self.fall_tau_s,
+            _ => return None,
+        };
+
+        Some(c64::real(value))
+    }
+}
+
+/// SPICE's `SFFM`: a sine of `amplitude` around `offset` at `carrier_hz`, its phase
+/// swinging by `modulation_index` radians at `signal_hz`.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Sffm {
+    pub offset: f64,
+    pub amplitude: f64,
+    pub carrier_hz: f64,
+    pub modulation_index: f64,
+    pub signal_hz: f64,
+}
+
+impl Sffm {
+    /// Reads `VO`, `VA`, `FC`, `MDI` and `FS`, of which the frequencies have to be
+    /// finite and not negative.
+    pub fn from_parameters(parameters: &mut Parameters) -> Self {
+        let sffm = Self {
+            offset: parameters.real_or("VO", 0.),
+            amplitude: parameters.real("VA"),
+            carrier_hz: parameters.real("FC"),
+            modulation_index: parameters.real_or("MDI", 0.),
+            signal_hz: parameters.real_or("FS", 0.),
+        };
+
+        for (parameter, frequency_hz) in [("FC", sffm.carrier_hz), ("FS", sffm.signal_hz)] {
+            if !(frequency_hz.is_finite() && frequency_hz >= 0.) {
+                parameters.invalid(parameter);
+            }
+        }
+
+        sffm
+    }
+
+    pub fn value(&self, t: f64) -> f64 {
+        let modulation = self.modulation_index * (2. * PI * self.signal_hz * t).sin();
+        self.offset + self.amplitude * (2. * PI * self.carrier_hz * t + modulation).sin()
+    }
+}
+
+impl Stimulus for Sffm {
+    type State = f64;
+    const VOLTAGE_PARAMETERS: &[&'static str] =
+        &["V", "I", "P", "VO", "VA", "FC", "MDI", "FS", "t"];
+
+    fn value_at(&self, _: &f64, t: f64) -> f64 {
+        self.value(t)
+    }
+
+    fn parameter(&self, parameter: &str, _: &str) -> Option<c64> {
+        let value = match parameter {
+            "VO" => self.offset,
+            "VA" => self.amplitude,
+            "FC" => self.carrier_hz,
+            "MDI" => self.modulation_index,
+            "FS" => self.signal_hz,
+            _ => return None,
+        };
+
+        Some(c64::real(value))
+    }
+}
+
+/// SPICE's `AM`: a sine at `carrier_hz` whose amplitude is `amplitude` times `offset`
+/// plus a sine at `modulating_hz`. It is zero until `delay_s`, where both start.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Am {
+    pub amplitude: f64,
+    pub offset: f64,
+    pub modulating_hz: f64,
+    pub carrier_hz: f64,
+    pub delay_s: f64,
+}
+
+impl Am {
+    /// Reads `VA`, `VO`, `MF`, `FC` and `TD`. The frequencies have to be finite and not
+    /// negative, and so does the delay.
+    pub fn from_parameters(parameters: &mut Parameters) -> Self {
+        let am = Self {
+            amplitude: parameters.real("VA"),
+            offset: parameters.real_or("VO", 0.),
+            modulating_hz: parameters.real("MF"),
+            carrier_hz: parameters.real("FC"),
+            delay_s: parameters.real_or("TD", 0.),
+        };
+
+        for (parameter, value) in [
+            ("MF", am.modulating_hz),
+            ("FC", am.carrier_hz),
+            ("TD", am.delay_s),
+        ] {
+            if !(value.is_finite() && value >= 0.) {
+                parameters.invalid(parameter);
+            }
+        }
+
+        am
+    }
+
+    pub fn value(&self, t: f64) -> f64 {
+        if t < self.delay_s {
+            return 0.;
+        }
+
+        let tau = t - self.delay_s;
+        let envelope = self.offset + (2. * PI * self.modulating_hz * tau).sin();
+        self.amplitude * envelope * (2. * PI * self.carrier_hz * tau).sin()
+    }
+
+    /// The delay, if it is after `t`.
+    pub fn next_corner(&self, t: f64) -> Option<f64> {
+        (!reached(self.delay_s, t)).then_some(self.delay_s)
+    }
+}
+
+impl Stimulus for Am {
+    type State = f64;
+    const VOLTAGE_PARAMETERS: &[&'static str] = &["V", "I", "P", "VA", "VO", "MF", "FC", "TD", "t"];
+
+    fn value_at(&self, _: &f64, t: f64) -> f64 {
+        self.value(t)
+    }
+
+    fn corner_after(&self, &t: &f64) -> Option<f64> {
+        self.next_corner(t)
+    }
+
+    fn parameter(&self, parameter: &str, _: &str) -> Option<c64> {
+        let value = match parameter {
+            "VA" => self.amplitude,
+            "VO" => self.offset,
+            "MF" => self.modulating_hz,
+            "FC" => self.carrier_hz,
+            "TD" => self.delay_s,
+            _ => return None,
+        };
+
+        Some(c64::real(value))
+    }
+}
+
+/// SPICE's `PWL`: interpolates linearly between `(time, value)` points kept in the
+/// circuit's [`Tables`], holding the first value before the first point and the last one
+/// after the last. With a finite `repeat_from_s`, it starts over from the first point at or
+/// after that time once it is through.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Pwl {
+    /// Times and values, interleaved.
+    pub points: Table,
+    pub repeat_from_s: f64,
+}
+
+/// The stretch of a [`Pwl`] between the points around the time it is at,
+/// held in the state so that stamping doesn't need the table.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default, PartialEq)]
+#[repr(C)]
+pub struct Segment {
+    pub start_s: f64,
+    pub start: f64,
+    pub end_s: f64,
+    pub end: f64,
+}
+
+impl Segment {
+    /// Interpolates at `t`, holding the end values outside the segment.
+    pub fn value(&self, t: f64) -> f64 {
+        if !(self.start_s.is_finite() && self.end_s.is_finite()) || self.end_s <= self.start_s {
+            return self.end;
+        }
+
+        let progress = ((t - self.start_s) / (self.end_s - self.start_s)).clamp(0., 1.);
+        self.start + (self.end - self.start) * progress
+    }
+}
+
+impl Pwl {
+    /// Reads the points as `T1`, `{quantity}1`, `T2`, `{quantity}2` and so on up to
+    /// the first missing time, which mustn't go back, and the time to repeat from as `R`.
+    pub fn from_parameters(
+        parameters: &mut Parameters,
+        quantity: &str,
+        tables: &mut Tables,
+    ) -> Self {
+        let mut points = vec![];
+        let mut previous_s = f64::NEG_INFINITY;
+
+        let mut n = 1;
+        while let Some(time) = parameters.take(&format!("T{n}")) {
+            if time.re < previous_s {
+                parameters.invalid(&format!("T{n}"));
+            }
+
+            previous_s = time.re;
+            points.extend([time.re, parameters.real(&format!("{quantity}{n}"))]);
+            n += 1;
+        }
+
+        if points.is_empty() {
+            // reports the first point as missing
+            parameters.real("T1");
+            parameters.real(&format!("{quantity}1"));
+        }
+
+        Self {
+            points: tables.push(points),
+            repeat_from_s: parameters.real_or("R", f64::INFINITY),
+        }
+    }
+
+    /// The segment `t` is in, ending at the first point not reached at `t`.
+    pub fn segment(&self, tables: &Tables, t: f64) -> Segment {
+        let values = tables.get(self.points);
+        let points = || values.chunks_exact(2).map(|point| (point[0], point[1]));
+
+        let Some((last_s, last)) = points().next_back() else {
+            return Segment::default();
+        };
+
+        // the points of a cycle after the first one are shifted by a period each
+        let repeat = points()
+            .position(|(time, _)| time >= self.repeat_from_s)
+            .filter(|&idx| values[2 * idx] < last_s);
+        let (skip, period) = repeat.map_or((0, 0.), |idx| (idx, last_s - values[2 * idx]));
+
+        let cycle = if period > 0. {
+            ((t - values[2 * skip]) / period).floor().max(0.)
+        } else {
+            0.
+        };
+
+        let cycle_points = |cycle: f64| {
+            let skip = if cycle == 0. { 0 } else { skip };
+            points()
+                .skip(skip)
+                .map(move |(time, value)| (time + cycle * period, value))
+        };
+
+        let upcoming = cycle_points(cycle).chain(
+            (period > 0.)
+                .then(|| cycle_points(cycle + 1.))
+                .into_iter()
+                .flatten(),
+        );
+
+        let mut previous = None;
+        for (time, value) in upcoming {
+            if !reached(time, t) {
+                let (start_s, start) = previous.unwrap_or((f64::NEG_INFINITY, value));
+                return Segment {
+                    start_s,
+                    start,
+                    end_s: time,
+                    end: value,
+                };
+            }
+
+            previous = Some((time, value));
+        }
+
+        Segment {
+            start_s: last_s,
+            start: last,
+            end_s: f64::INFINITY,
+            end: last,
+        }
+    }
+}
+
+/// The time a [`Pwl`] source is at and the segment around it.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct PwlState {
+    pub t: f64,
+    pub segment: Segment,
+}
+
+impl PwlState {
+    pub fn value(&self) -> f64 {
+        self.segment.value(self.t)
+    }
+}
+
+impl Clock for PwlState {
+    fn time(&self) -> f64 {
+        self.t
+    }
+
+    fn time_mut(&mut self) -> &mut f64 {
+        &mut self.t
+    }
+}
+
+impl Stimulus for Pwl {
+    type State = PwlState;
+    const VOLTAGE_PARAMETERS: &[&'static str] = &["V", "I", "P", "R", "t"];
+    const CURRENT_PARAMETERS: &[&'static str] = &["I", "V", "P", "R", "t"];
+
+    fn value_at(&self, state: &PwlState, t: f64) -> f64 {
+        state.segment.value(t)
+    }
+
+    /// The end of the segment.
+    fn corner_after(&self, state: &PwlState) -> Option<f64> {
+        Some(state.segment.end_s)
+    }
+
+    fn load(&self, tables: &Tables, state: &mut PwlState) {
+        state.segment = self.segment(tables, state.t);
+    }
+
+    fn parameter(&self, parameter: &str, _: &str) -> Option<c64> {
+        match parameter {
+            "R" => Some(c64::real(self.repeat_from_s)),
+            _ => None,
+        }
+    }
+}
+
+/// Voltage sources report as `I` the current they drive out of their
+/// positive terminal, so `P` is the power delivered to the circuit.
 #[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
 #[repr(C)]
 pub struct DC1Source {
     pub voltage_volt: f64,
 }
 
+impl FromParameters for DC1Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            voltage_volt: parameters.real("V"),
+        }
+    }
+}
+
 impl Component for DC1Source {
     type State = ();
-    const TERMINAL_COUNT: usize = 1;
+    const TERMINAL_COUNT: usize = 2;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(1);
     const PRIORITY: usize = 25;
-    const PARAMETERS: &[&'static str] = &["V", "P"];
+    const PARAMETERS: &[&'static str] = &["V", "I", "P"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 1), (1, 0)];
 
-    fn stamp(&self, net: &mut LinearEquations, _: f64, [n]: [u32; 1], _: &Self::State) {
-        net.clear_row(n);
-        net.add_a(n, n, c64::ONE);
-        net.set_b(n, c64::new(self.voltage_volt, 0.));
+    fn stamp(&self, net: &mut LinearEquations, mode: Mode, [n, k]: [u32; 2], _: &Self::State) {
+        net.stamp_voltage_source(n, None, k, dc(self.voltage_volt, mode));
     }
 
     fn parameter(
         &self,
-        _: &LinearEquations,
-        _: [u32; Self::TERMINAL_COUNT],
+        net: &LinearEquations,
+        [_, k]: [u32; Self::TERMINAL_COUNT],
         _: &Self::State,
         parameter: &str,
     ) -> Option<c64> {
         let v = c64::new(self.voltage_volt, 0.);
+        let i = -net.get_branch_current(k);
+
         match parameter {
             "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
             _ => None,
         }
     }
 }
 
+/// The reference node: its row of the equations is replaced by `v = 0`, which drops
+/// the node's current law like eliminating it would. It is stamped after everything
+/// else, so any number of grounds on any nets keep the equations regular.
 #[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
 #[repr(C)]
 pub struct Ground;
 
+impl FromParameters for Ground {
+    fn from_parameters(_: &mut Parameters) -> Self {
+        Ground
+    }
+}
+
 impl Component for Ground {
     type State = ();
     const TERMINAL_COUNT: usize = 1;
-    const PRIORITY: usize = 25;
+    const PRIORITY: usize = 30;
 
     fn stamp(
         &self,
         net: &mut LinearEquations,
-        _: f64,
+        _: Mode,
         [n]: [u32; Self::TERMINAL_COUNT],
         _: &Self::State,
     ) {
@@ -70,42 +765,64 @@ pub struct AC1Source {
     pub phase_rad: f64,
 }
 
+impl FromParameters for AC1Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            amplitude_volt: parameters.real("V"),
+            frequency_hz: parameters.real("f"),
+            phase_rad: parameters.real_or("phi", 0.),
+        }
+    }
+}
+
 impl Component for AC1Source {
     type State = f64;
-    const TERMINAL_COUNT: usize = 1;
+    const TERMINAL_COUNT: usize = 2;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(1);
     const PRIORITY: usize = 25;
-    const PARAMETERS: &[&'static str] = &["V", "P", "f", "phi", "t"];
-
-    fn stamp(&self, net: &mut LinearEquations, _: f64, [n]: [u32; 1], t: &Self::State) {
-        net.clear_row(n);
-        net.add_a(n, n, c64::ONE);
+    const PARAMETERS: &[&'static str] = &["V", "I", "P", "f", "phi", "t"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 1), (1, 0)];
 
-        let angle = 2.0 * PI * self.frequency_hz * t + self.phase_rad;
-        let voltage = c64::polar(self.amplitude_volt, angle);
+    fn stamp(&self, net: &mut LinearEquations, mode: Mode, [n, k]: [u32; 2], &t: &Self::State) {
+        let voltage = sinusoid(
+            self.amplitude_volt,
+            self.frequency_hz,
+            self.phase_rad,
+            t,
+            mode,
+        );
 
-        net.set_b(n, voltage);
+        net.stamp_voltage_source(n, None, k, voltage);
     }
 
     fn post_stamp(
         &self,
         _net: &LinearEquations,
-        dt: f64,
+        mode: Mode,
         _terminals: [u32; Self::TERMINAL_COUNT],
-        _state: &mut Self::State,
+        t: &mut Self::State,
     ) {
-        *_state += dt;
+        if let Mode::Transient { dt, .. } = mode {
+            *t += dt;
+        }
     }
 
     fn parameter(
         &self,
-        _: &LinearEquations,
-        _: [u32; Self::TERMINAL_COUNT],
+        net: &LinearEquations,
+        [_, k]: [u32; Self::TERMINAL_COUNT],
         &t: &Self::State,
         parameter: &str,
     ) -> Option<c64> {
         let v = c64::new(self.amplitude_volt, 0.);
+        let angle = 2.0 * PI * self.frequency_hz * t + self.phase_rad;
+        let i = -net.get_branch_current(k);
+
         match parameter {
             "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(c64::polar(self.amplitude_volt, angle) * i),
             "f" => Some(c64::new(self.frequency_hz, 0.)),
             "phi" => Some(c64::new(self.phase_rad, 0.)),
             "t" => Some(c64::new(t, 0.)),
@@ -113,3 +830,1059 @@ impl Component for AC1Source {
         }
     }
 }
+
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct DC2Source {
+    pub voltage_volt: f64,
+}
+
+impl FromParameters for DC2Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            voltage_volt: parameters.real("V"),
+        }
+    }
+}
+
+impl Component for DC2Source {
+    type State = ();
+    const TERMINAL_COUNT: usize = 3;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(2);
+    const PRIORITY: usize = 25;
+    const PARAMETERS: &[&'static str] = &["V", "I", "P"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 2), (1, 2), (2, 0), (2, 1)];
+
+    fn stamp(&self, net: &mut LinearEquations, mode: Mode, [p, n, k]: [u32; 3], _: &Self::State) {
+        net.stamp_voltage_source(p, Some(n), k, dc(self.voltage_volt, mode));
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [_, _, k]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = c64::new(self.voltage_volt, 0.);
+        let i = -net.get_branch_current(k);
+
+        match parameter {
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(v * i),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct AC2Source {
+    pub amplitude_volt: f64,
+    pub frequency_hz: f64,
+    pub phase_rad: f64,
+}
+
+impl FromParameters for AC2Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            amplitude_volt: parameters.real("V"),
+            frequency_hz: parameters.real("f"),
+            phase_rad: parameters.real_or("phi", 0.),
+        }
+    }
+}
+
+impl Component for AC2Source {
+    type State = f64;
+    const TERMINAL_COUNT: usize = 3;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(2);
+    const PRIORITY: usize = 25;
+    const PARAMETERS: &[&'static str] = &["V", "I", "P", "f", "phi", "t"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 2), (1, 2), (2, 0), (2, 1)];
+
+    fn stamp(&self, net: &mut LinearEquations, mode: Mode, [p, n, k]: [u32; 3], &t: &Self::State) {
+        let voltage = sinusoid(
+            self.amplitude_volt,
+            self.frequency_hz,
+            self.phase_rad,
+            t,
+            mode,
+        );
+
+        net.stamp_voltage_source(p, Some(n), k, voltage);
+    }
+
+    fn post_stamp(
+        &self,
+        _net: &LinearEquations,
+        mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        t: &mut Self::State,
+    ) {
+        if let Mode::Transient { dt, .. } = mode {
+            *t += dt;
+        }
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [_, _, k]: [u32; Self::TERMINAL_COUNT],
+        &t: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = c64::new(self.amplitude_volt, 0.);
+        let angle = 2.0 * PI * self.frequency_hz * t + self.phase_rad;
+        let i = -net.get_branch_current(k);
+
+        match parameter {
+            "V" => Some(v),
+            "I" => Some(i),
+            "P" => Some(c64::polar(self.amplitude_volt, angle) * i),
+            "f" => Some(c64::new(self.frequency_hz, 0.)),
+            "phi" => Some(c64::new(self.phase_rad, 0.)),
+            "t" => Some(c64::new(t, 0.)),
+            _ => None,
+        }
+    }
+}
+
+/// Current sources drive `I` out of their positive terminal, through the
+/// circuit and back into the negative one.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct DCCurrentSource {
+    pub current_ampere: f64,
+}
+
+impl FromParameters for DCCurrentSource {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            current_ampere: parameters.real("I"),
+        }
+    }
+}
+
+impl Component for DCCurrentSource {
+    type State = ();
+    const TERMINAL_COUNT: usize = 2;
+    const PRIORITY: usize = 25;
+    const PARAMETERS: &[&'static str] = &["I", "V", "P"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (1, 1)];
+
+    fn stamp(&self, net: &mut LinearEquations, mode: Mode, [p, n]: [u32; 2], _: &Self::State) {
+        let i = dc(self.current_ampere, mode);
+
+        net.add_b(p, i);
+        net.add_b(n, -i);
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [p, n]: [u32; Self::TERMINAL_COUNT],
+        _: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let i = c64::new(self.current_ampere, 0.);
+        let v = net.get_voltage_across(p, n);
+
+        match parameter {
+            "I" => Some(i),
+            "V" => Some(v),
+            "P" => Some(v * i),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct ACCurrentSource {
+    pub amplitude_ampere: f64,
+    pub frequency_hz: f64,
+    pub phase_rad: f64,
+}
+
+impl FromParameters for ACCurrentSource {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            amplitude_ampere: parameters.real("I"),
+            frequency_hz: parameters.real("f"),
+            phase_rad: parameters.real_or("phi", 0.),
+        }
+    }
+}
+
+impl Component for ACCurrentSource {
+    type State = f64;
+    const TERMINAL_COUNT: usize = 2;
+    const PRIORITY: usize = 25;
+    const PARAMETERS: &[&'static str] = &["I", "V", "P", "f", "phi", "t"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (1, 1)];
+
+    fn stamp(&self, net: &mut LinearEquations, mode: Mode, [p, n]: [u32; 2], &t: &Self::State) {
+        let i = sinusoid(
+            self.amplitude_ampere,
+            self.frequency_hz,
+            self.phase_rad,
+            t,
+            mode,
+        );
+
+        net.add_b(p, i);
+        net.add_b(n, -i);
+    }
+
+    fn post_stamp(
+        &self,
+        _net: &LinearEquations,
+        mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        t: &mut Self::State,
+    ) {
+        if let Mode::Transient { dt, .. } = mode {
+            *t += dt;
+        }
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [p, n]: [u32; Self::TERMINAL_COUNT],
+        &t: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let angle = 2.0 * PI * self.frequency_hz * t + self.phase_rad;
+        let v = net.get_voltage_across(p, n);
+
+        match parameter {
+            "I" => Some(c64::new(self.amplitude_ampere, 0.)),
+            "V" => Some(v),
+            "P" => Some(v * c64::polar(self.amplitude_ampere, angle)),
+            "f" => Some(c64::new(self.frequency_hz, 0.)),
+            "phi" => Some(c64::new(self.phase_rad, 0.)),
+            "t" => Some(c64::new(t, 0.)),
+            _ => None,
+        }
+    }
+}
+
+/// `parameter` of a source driving `stimulus` as `quantity`, with `response` the other
+/// one of its voltage and current.
+fn stimulus_parameter<S: Stimulus>(
+    stimulus: &S,
+    state: &S::State,
+    quantity: &str,
+    response: c64,
+    parameter: &str,
+) -> Option<c64> {
+    let driven = c64::real(stimulus.value_at(state, state.time()));
+    let (v, i) = match quantity {
+        "V" => (driven, response),
+        _ => (response, driven),
+    };
+
+    match parameter {
+        "V" => Some(v),
+        "I" => Some(i),
+        "P" => Some(v * i),
+        "t" => Some(c64::real(state.time())),
+        _ => stimulus.parameter(parameter, quantity),
+    }
+}
+
+/// A voltage source from its terminal to ground following a [`Stimulus`].
+#[derive(Debug, Pod, Zeroable, Clone, Copy)]
+#[repr(transparent)]
+pub struct Stimulus1Source<S> {
+    pub stimulus: S,
+}
+
+impl<S: Stimulus> Component for Stimulus1Source<S> {
+    type State = S::State;
+    const TERMINAL_COUNT: usize = 2;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(1);
+    const PRIORITY: usize = 25;
+    const PARAMETERS: &[&'static str] = S::VOLTAGE_PARAMETERS;
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 1), (1, 0)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        // the terminal count is generic to the compiler, so the array can't be destructured
+        let (n, k) = (terminals[0], terminals[1]);
+        net.stamp_voltage_source(n, None, k, self.stimulus.at(state, mode));
+    }
+
+    fn post_stamp(
+        &self,
+        _net: &LinearEquations,
+        mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+    ) {
+        state.advance(mode);
+    }
+
+    fn load(&self, tables: &Tables, state: &mut Self::State) {
+        self.stimulus.load(tables, state);
+    }
+
+    fn breakpoint(
+        &self,
+        _net: &LinearEquations,
+        mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) -> Option<f64> {
+        self.stimulus.breakpoint(state, mode)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let i = -net.get_branch_current(terminals[1]);
+        stimulus_parameter(&self.stimulus, state, "V", i, parameter)
+    }
+}
+
+/// A floating voltage source following a [`Stimulus`].
+#[derive(Debug, Pod, Zeroable, Clone, Copy)]
+#[repr(transparent)]
+pub struct Stimulus2Source<S> {
+    pub stimulus: S,
+}
+
+impl<S: Stimulus> Component for Stimulus2Source<S> {
+    type State = S::State;
+    const TERMINAL_COUNT: usize = 3;
+    const BRANCH_COUNT: usize = 1;
+    const CURRENT_BRANCH: Option<usize> = Some(2);
+    const PRIORITY: usize = 25;
+    const PARAMETERS: &[&'static str] = S::VOLTAGE_PARAMETERS;
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 2), (1, 2), (2, 0), (2, 1)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        let (p, n, k) = (terminals[0], terminals[1], terminals[2]);
+        net.stamp_voltage_source(p, Some(n), k, self.stimulus.at(state, mode));
+    }
+
+    fn post_stamp(
+        &self,
+        _net: &LinearEquations,
+        mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+    ) {
+        state.advance(mode);
+    }
+
+    fn load(&self, tables: &Tables, state: &mut Self::State) {
+        self.stimulus.load(tables, state);
+    }
+
+    fn breakpoint(
+        &self,
+        _net: &LinearEquations,
+        mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) -> Option<f64> {
+        self.stimulus.breakpoint(state, mode)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let i = -net.get_branch_current(terminals[2]);
+        stimulus_parameter(&self.stimulus, state, "V", i, parameter)
+    }
+}
+
+/// A current source following a [`Stimulus`].
+#[derive(Debug, Pod, Zeroable, Clone, Copy)]
+#[repr(transparent)]
+pub struct StimulusCurrentSource<S> {
+    pub stimulus: S,
+}
+
+impl<S: Stimulus> Component for StimulusCurrentSource<S> {
+    type State = S::State;
+    const TERMINAL_COUNT: usize = 2;
+    const PRIORITY: usize = 25;
+    const PARAMETERS: &[&'static str] = S::CURRENT_PARAMETERS;
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (1, 1)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        mode: Mode,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        let (p, n) = (terminals[0], terminals[1]);
+        let i = self.stimulus.at(state, mode);
+
+        net.add_b(p, i);
+        net.add_b(n, -i);
+    }
+
+    fn post_stamp(
+        &self,
+        _net: &LinearEquations,
+        mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+    ) {
+        state.advance(mode);
+    }
+
+    fn load(&self, tables: &Tables, state: &mut Self::State) {
+        self.stimulus.load(tables, state);
+    }
+
+    fn breakpoint(
+        &self,
+        _net: &LinearEquations,
+        mode: Mode,
+        _terminals: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) -> Option<f64> {
+        self.stimulus.breakpoint(state, mode)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let v = net.get_voltage_across(terminals[0], terminals[1]);
+        stimulus_parameter(&self.stimulus, state, "I", v, parameter)
+    }
+}
+
+pub type Pulse1Source = Stimulus1Source<Pulse>;
+pub type Pulse2Source = Stimulus2Source<Pulse>;
+pub type PulseCurrentSource = StimulusCurrentSource<Pulse>;
+pub type Pwl1Source = Stimulus1Source<Pwl>;
+pub type Pwl2Source = Stimulus2Source<Pwl>;
+pub type PwlCurrentSource = StimulusCurrentSource<Pwl>;
+pub type Exp1Source = Stimulus1Source<Exponential>;
+pub type Exp2Source = Stimulus2Source<Exponential>;
+pub type Sffm1Source = Stimulus1Source<Sffm>;
+pub type Sffm2Source = Stimulus2Source<Sffm>;
+pub type Am1Source = Stimulus1Source<Am>;
+pub type Am2Source = Stimulus2Source<Am>;
+
+impl FromParameters for Pulse1Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            stimulus: Pulse::from_parameters(parameters, "V"),
+        }
+    }
+}
+
+impl FromParameters for Pulse2Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            stimulus: Pulse::from_parameters(parameters, "V"),
+        }
+    }
+}
+
+impl FromParameters for PulseCurrentSource {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            stimulus: Pulse::from_parameters(parameters, "I"),
+        }
+    }
+}
+
+impl FromParameters for Exp1Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            stimulus: Exponential::from_parameters(parameters),
+        }
+    }
+}
+
+impl FromParameters for Exp2Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            stimulus: Exponential::from_parameters(parameters),
+        }
+    }
+}
+
+impl FromParameters for Sffm1Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            stimulus: Sffm::from_parameters(parameters),
+        }
+    }
+}
+
+impl FromParameters for Sffm2Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            stimulus: Sffm::from_parameters(parameters),
+        }
+    }
+}
+
+impl FromParameters for Am1Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            stimulus: Am::from_parameters(parameters),
+        }
+    }
+}
+
+impl FromParameters for Am2Source {
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        Self {
+            stimulus: Am::from_parameters(parameters),
+        }
+    }
+}
+
+/// The shape a [`FunctionGenerator`] repeats, reported as its `shape` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveshape {
+    /// High for the duty cycle of each period, low for the rest.
+    Square = 0,
+    /// Rises over the duty cycle of each period and falls over the rest.
+    Triangle = 1,
+    /// Rises over the whole period and drops back at its end.
+    Sawtooth = 2,
+}
+
+impl TryFrom<u64> for Waveshape {
+    type Error = u64;
+
+    fn try_from(shape: u64) -> Result<Self, Self::Error> {
+        match shape {
+            0 => Ok(Waveshape::Square),
+            1 => Ok(Waveshape::Triangle),
+            2 => Ok(Waveshape::Sawtooth),
+            unknown => Err(unknown),
+        }
+    }
+}
+
+/// A periodic `shape` swinging `amplitude_volt` around `offset_volt`, starting each
+/// period low, or high for a square. With a finite `bursts` it stops after that many
+/// periods and rests at the offset.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct FunctionGenerator {
+    /// A [`Waveshape`].
+    pub shape: u64,
+    pub amplitude_volt: f64,
+    pub frequency_hz: f64,
+    pub offset_volt: f64,
+    /// The fraction of each period a square is high or a triangle rises for.
+    pub duty_cycle: f64,
+    pub bursts: f64,
+}
+
+impl FunctionGenerator {
+    /// Reads the amplitude `Va`, `f`, the offset `Vo`, the duty cycle `D` and the burst
+    /// count `N`. The frequency has to be positive and finite, the duty cycle a fraction
+    /// and the burst count a positive whole number, or left out to go on forever.
+    pub fn from_parameters(parameters: &mut Parameters, shape: Waveshape) -> Self {
+        let generator = Self {
+            shape: shape as u64,
+            amplitude_volt: parameters.real("Va"),
+            frequency_hz: parameters.real("f"),
+            offset_volt: parameters.real_or("Vo", 0.),
+            duty_cycle: parameters.real_or("D", 0.5),
+            bursts: parameters.real_or("N", f64::INFINITY),
+        };
+
+        if !(generator.frequency_hz.is_finite() && generator.frequency_hz > 0.) {
+            parameters.invalid("f");
+        }
+        if !(0. ..=1.).contains(&generator.duty_cycle) {
+            parameters.invalid("D");
+        }
+
+        let whole = generator.bursts >= 1. && generator.bursts.fract() == 0.;
+        if !whole && generator.bursts != f64::INFINITY {
+            parameters.invalid("N");
+        }
+
+        generator
+    }
+
+    /// Panics if `shape` isn't one of the [`Waveshape`]s.
+    pub fn shape(&self) -> Waveshape {
+        Waveshape::try_from(self.shape).unwrap_or_else(|shape| panic!("{shape} is not a waveshape"))
+    }
+
+    fn period_s(&self) -> f64 {
+        1. / self.frequency_hz
+    }
+
+    /// The period `t` is in, counting from zero, and when it started.
+    fn cycle(&self, t: f64) -> (f64, f64) {
+        let period_s = self.period_s();
+
+        let mut cycle = (t / period_s).floor().max(0.);
+        if reached((cycle + 1.) * period_s, t) {
+            cycle += 1.;
+        }
+
+        (cycle, cycle * period_s)
+    }
+
+    /// When the shape turns around within the period starting at `start_s`.
+    fn turn_s(&self, start_s: f64) -> f64 {
+        match self.shape() {
+            Waveshape::Sawtooth => start_s + self.period_s(),
+            _ => start_s + self.duty_cycle * self.period_s(),
+        }
+    }
+
+    pub fn value(&self, t: f64) -> f64 {
+        let (cycle, start_s) = self.cycle(t);
+        if cycle >= self.bursts {
+            return self.offset_volt;
+        }
+
+        let turn_s = self.turn_s(start_s);
+        let end_s = start_s + self.period_s();
+
+        // from -1 to 1 over the period
+        let swing = match self.shape() {
+            Waveshape::Square if reached(turn_s, t) => -1.,
+            Waveshape::Square => 1.,
+            _ if t < turn_s => -1. + 2. * (t - start_s) / (turn_s - start_s),
+            _ => 1. - 2. * (t - turn_s) / (end_s - turn_s),
+        };
+
+        self.offset_volt + self.amplitude_volt * swing
+    }
+
+    /// The first corner of the waveform after `t`.
+    pub fn next_corner(&self, t: f64) -> Option<f64> {
+        let (cycle, start_s) = self.cycle(t);
+        if cycle >= self.bursts {
+            return None;
+        }
+
+        [self.turn_s(start_s), start_s + self.period_s()]
+            .into_iter()
+            .find(|&corner| !reached(corner, t))
+    }
+}
+
+impl Stimulus for FunctionGenerator {
+    type State = f64;
+    const VOLTAGE_PARAMETERS: &[&'static str] =
+        &["V", "I", "P", "Va", "f", "Vo", "D", "N", "shape", "t"];
+
+    fn value_at(&self, _: &f64, t: f64) -> f64 {
+        self.value(t)
+    }
+
+    /// The offset it swings around.
+    fn dc_value(&self, _: &f64) -> f64 {
+        self.offset_volt
+    }
+
+    fn corner_after(&self, &t: &f64) -> Option<f64> {
+        self.next_corner(t)
+    }
+
+    fn parameter(&self, parameter: &str, _: &str) -> Option<c64> {
+        let value = match parameter {
+            "Va" => self.amplitude_volt,
+            "f" => self.frequency_hz,
+            "Vo" => self.offset_volt,
+            "D" => self.duty_cycle,
+            "N" => self.bursts,
+            "shape" => self.shape as f64,
+            _ => return None,
+        };
+
+        Some(c64::real(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CircuitBuilder, ComponentLibrary, Parser, Probe, Timestep, Transient, Waveform,
+        circuit::Circuit,
+        component::{ComponentError, Integration},
+        parser::{Command, build, invalid_parameters},
+    };
+
+    use super::*;
+
+    /// Runs `netlist` with fixed steps of 0.7us, which the breakpoints of the source
+    /// in it have to cut short to land on its corners, probing `V(in)` and `probes`.
+    fn run(netlist: &str, stop_s: f64, probes: impl IntoIterator<Item = Probe>) -> Waveform {
+        let transient = Transient {
+            start_s: 0.,
+            stop_s,
+            step_s: 0.7e-6,
+            timestep: Timestep::Fixed,
+            integration: Integration::BackwardEuler,
+            probes: [Probe::voltage("in")].into_iter().chain(probes).collect(),
+        };
+
+        transient.run(&mut build(netlist)).unwrap()
+    }
+
+    /// `V(in)` at the time point on `t`, which there has to be.
+    fn sample_at(waveform: &Waveform, t: f64) -> f64 {
+        let idx = waveform
+            .time
+            .iter()
+            .position(|&time| (time - t).abs() < 1e-15)
+            .unwrap_or_else(|| panic!("no time point at {t}"));
+
+        waveform.column("V(in)").unwrap()[idx].re
+    }
+
+    const CLOCK: Pulse = Pulse {
+        initial: 0.,
+        pulsed: 1.,
+        delay_s: 1e-6,
+        rise_s: 1e-6,
+        fall_s: 1e-6,
+        width_s: 3e-6,
+        period_s: 10e-6,
+    };
+
+    #[test]
+    fn test_pulse_shape() {
+        let times = [0., 1.5e-6, 3e-6, 5.5e-6, 8e-6, 11.5e-6];
+        for (t, expected) in times.into_iter().zip([0., 0.5, 1., 0.5, 0., 0.5]) {
+            assert!((CLOCK.value(t) - expected).abs() < 1e-12, "at {t}");
+        }
+
+        assert_eq!(CLOCK.next_corner(0.), Some(1e-6));
+        assert_eq!(CLOCK.next_corner(1e-6), Some(2e-6));
+        assert!((CLOCK.next_corner(7e-6).unwrap() - 11e-6).abs() < 1e-18);
+
+        let step = Pulse {
+            width_s: f64::INFINITY,
+            period_s: f64::INFINITY,
+            ..CLOCK
+        };
+        assert_eq!(step.value(1e3), 1.);
+        assert_eq!(step.next_corner(2e-6), None);
+    }
+
+    #[test]
+    fn test_pulse_source_corners() {
+        let netlist = r#"
+            pulse-source-1-terminal "V1"    in          V1=0 V2=1 TD=1u TR=1u TF=1u PW=3u PER=10u
+            resistor                        in gnd      R=1k
+            ground                          gnd
+        "#;
+        let waveform = run(netlist, 25e-6, []);
+
+        for (corner, expected) in [1., 2., 5., 6., 11., 12., 15., 16., 21., 22.]
+            .into_iter()
+            .zip([0., 1., 1., 0., 0., 1., 1., 0., 0., 1.])
+        {
+            let v = sample_at(&waveform, corner * 1e-6);
+            assert!((v - expected).abs() < 1e-9, "{v} at {corner}us");
+        }
+
+        assert_eq!(
+            invalid_parameters("pulse-source-1-terminal a V1=0 V2=1 TD=-1u TF=-1u PER=0"),
+            ["TD", "TF", "PER"]
+        );
+        // the period is shorter than the pulse
+        assert_eq!(
+            invalid_parameters("pulse-current-source a b I1=0 I2=1 TR=1u PW=2u PER=2u"),
+            ["PER"]
+        );
+    }
+
+    #[test]
+    fn test_pwl_segments() {
+        let mut tables = Tables::default();
+        let pwl = Pwl {
+            points: tables.push([1., 0., 2., 1., 4., -1.]),
+            repeat_from_s: f64::INFINITY,
+        };
+
+        let before = pwl.segment(&tables, 0.5);
+        assert_eq!((before.end_s, before.value(0.5)), (1., 0.));
+
+        let rising = pwl.segment(&tables, 1.);
+        assert_eq!((rising.start_s, rising.end_s), (1., 2.));
+        assert_eq!(rising.value(1.5), 0.5);
+        assert_eq!(pwl.segment(&tables, 3.).value(3.), 0.);
+
+        let after = pwl.segment(&tables, 4.);
+        assert_eq!((after.end_s, after.value(10.)), (f64::INFINITY, -1.));
+
+        // repeats 2s to 4s, every 2s
+        let pwl = Pwl {
+            repeat_from_s: 1.5,
+            ..pwl
+        };
+        let repeated = pwl.segment(&tables, 4.5);
+        assert_eq!((repeated.start_s, repeated.end_s), (4., 6.));
+        assert_eq!(repeated.value(5.), 0.);
+        let repeated = pwl.segment(&tables, 6.);
+        assert_eq!((repeated.start_s, repeated.end_s), (6., 8.));
+        assert_eq!(repeated.value(6.), 1.);
+    }
+
+    #[test]
+    fn test_pwl_source_corners() {
+        let netlist = r#"
+            pwl-source-1-terminal   "V1"    in          T1=1u V1=0 T2=2u V2=1 T3=3u V3=0 R=1u
+            resistor                        in gnd      R=1k
+            ground                          gnd
+        "#;
+        let waveform = run(netlist, 10e-6, []);
+
+        // a triangle repeating every 2us
+        for (corner, expected) in [1., 2., 3., 4., 5., 6., 7., 8., 9.]
+            .into_iter()
+            .zip([0., 1., 0., 1., 0., 1., 0., 1., 0.])
+        {
+            let v = sample_at(&waveform, corner * 1e-6);
+            assert!((v - expected).abs() < 1e-9, "{v} at {corner}us");
+        }
+
+        // a table without points is missing its first one
+        let mut builder = CircuitBuilder::new();
+        builder.add_commands(
+            Parser::from("pwl-source-1-terminal a")
+                .parse_commands()
+                .unwrap(),
+        );
+        assert!(builder.build(&ComponentLibrary::with_builtins()).is_err());
+
+        // one going back in time is invalid, and leaves nothing in the tables
+        let [Command::Component { parameters, .. }] =
+            &Parser::from("pwl-source-1-terminal a T1=1u V1=0 T2=3u V2=1 T3=2u V3=0")
+                .parse_commands()
+                .unwrap()[..]
+        else {
+            panic!("expected a single component");
+        };
+
+        let mut circuit = Circuit::new();
+        let errors = ComponentLibrary::with_builtins()
+            .construct(
+                "pwl-source-1-terminal",
+                &mut circuit,
+                None,
+                &[0],
+                parameters.clone(),
+            )
+            .unwrap()
+            .unwrap_err();
+
+        assert!(matches!(
+            &errors[..],
+            [ComponentError::InvalidParameter { parameter }] if parameter == "T3"
+        ));
+        assert_eq!(circuit.tables.push([]), Tables::default().push([]));
+    }
+
+    #[test]
+    fn test_function_generator_shapes() {
+        let square = FunctionGenerator {
+            shape: Waveshape::Square as u64,
+            amplitude_volt: 1.,
+            frequency_hz: 1e3,
+            offset_volt: 0.5,
+            duty_cycle: 0.25,
+            bursts: 2.,
+        };
+        let times = [0., 0.2e-3, 0.25e-3, 0.9e-3, 1e-3, 1.3e-3, 2e-3, 5e-3];
+        for (t, expected) in times
+            .into_iter()
+            .zip([1.5, 1.5, -0.5, -0.5, 1.5, -0.5, 0.5, 0.5])
+        {
+            assert!((square.value(t) - expected).abs() < 1e-12, "at {t}");
+        }
+        assert_eq!(square.next_corner(0.), Some(0.25e-3));
+        assert_eq!(square.next_corner(0.25e-3), Some(1e-3));
+        assert_eq!(square.next_corner(2e-3), None);
+
+        let triangle = FunctionGenerator {
+            shape: Waveshape::Triangle as u64,
+            bursts: f64::INFINITY,
+            ..square
+        };
+        let times = [0., 0.125e-3, 0.25e-3, 0.625e-3, 10e-3];
+        for (t, expected) in times.into_iter().zip([-0.5, 0.5, 1.5, 0.5, -0.5]) {
+            assert!((triangle.value(t) - expected).abs() < 1e-9, "at {t}");
+        }
+
+        let sawtooth = FunctionGenerator {
+            shape: Waveshape::Sawtooth as u64,
+            ..triangle
+        };
+        assert!((sawtooth.value(0.5e-3) - 0.5).abs() < 1e-12);
+        assert_eq!(sawtooth.next_corner(0.5e-3), Some(1e-3));
+
+        // the operating point sees the offset, not where the first period starts
+        assert_eq!(square.dc_value(&0.), 0.5);
+        assert_eq!(sawtooth.dc_value(&0.), 0.5);
+
+        assert_eq!(Waveshape::try_from(2), Ok(Waveshape::Sawtooth));
+        assert_eq!(Waveshape::try_from(3), Err(3));
+    }
+
+    #[test]
+    fn test_function_generator_burst() {
+        let netlist = r#"
+            square-generator        "V1"    in          Va=1 f=100k D=0.3 N=3
+            resistor                        in gnd      R=1k
+            ground                          gnd
+        "#;
+        let probes = ["D", "V", "I", "P"].map(|parameter| Probe::parameter("V1", parameter));
+        let waveform = run(netlist, 50e-6, probes);
+        assert_eq!(waveform.column("D(V1)").unwrap()[0].re, 0.3);
+
+        // it reports what it puts out, delivering it into the load
+        let [v, source, i, p] =
+            ["V(in)", "V(V1)", "I(V1)", "P(V1)"].map(|probe| waveform.column(probe).unwrap());
+        for idx in 0..waveform.time.len() {
+            assert!((source[idx] - v[idx]).norm() < 1e-12);
+            assert!((i[idx].re - v[idx].re / 1e3).abs() < 1e-12);
+            assert!((p[idx] - source[idx] * i[idx]).norm() < 1e-12);
+        }
+
+        for (corner, expected) in [3., 10., 13., 20., 23., 30.]
+            .into_iter()
+            .zip([-1., 1., -1., 1., -1., 0.])
+        {
+            let v = sample_at(&waveform, corner * 1e-6);
+            assert!((v - expected).abs() < 1e-9, "{v} at {corner}us");
+        }
+
+        // rests at the offset after the burst
+        assert_eq!(v.last().unwrap().re, 0.);
+
+        assert_eq!(
+            invalid_parameters("square-generator a Va=1 f=0 D=1.5 N=2.5"),
+            ["f", "D", "N"]
+        );
+        for bursts in ["0", "-1", "0.5"] {
+            assert_eq!(
+                invalid_parameters(&format!("square-generator a Va=1 f=1k N={bursts}")),
+                ["N"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_modulated_shapes() {
+        let sffm = Sffm {
+            offset: 1.,
+            amplitude: 2.,
+            carrier_hz: 1e3,
+            modulation_index: 0.5,
+            signal_hz: 100.,
+        };
+        let t = 0.3e-3;
+        let expected = 1. + 2. * (2. * PI * 0.3 + 0.5 * (2. * PI * 0.03f64).sin()).sin();
+        assert!((sffm.value(t) - expected).abs() < 1e-12);
+
+        let am = Am {
+            amplitude: 2.,
+            offset: 1.,
+            modulating_hz: 100.,
+            carrier_hz: 1e3,
+            delay_s: 1e-3,
+        };
+        assert_eq!(am.value(0.5e-3), 0.);
+        assert_eq!(am.next_corner(0.), Some(1e-3));
+        assert_eq!(am.next_corner(1e-3), None);
+        // a quarter carrier period in, where the carrier peaks
+        let expected = 2. * (1. + (2. * PI * 0.025f64).sin());
+        assert!((am.value(1.25e-3) - expected).abs() < 1e-12);
+
+        assert_eq!(
+            invalid_parameters("sffm-source-1-terminal a VA=1 FC=1/0 FS=-1"),
+            ["FC", "FS"]
+        );
+        assert_eq!(
+            invalid_parameters("am-source-1-terminal a VA=1 MF=-1 FC=-1k TD=-1m"),
+            ["MF", "FC", "TD"]
+        );
+    }
+
+    #[test]
+    fn test_exp_source() {
+        let netlist = r#"
+            dc-source-1-terminal            ref         V=1
+            exp-source-2-terminal   "V1"    in ref      V1=0 V2=2 TD1=1u TAU1=1u TD2=4.5u TAU2=0.5u
+            resistor                        in gnd      R=1k
+            ground                          gnd
+        "#;
+        let waveform = run(netlist, 8e-6, [Probe::parameter("V1", "V")]);
+        let v = waveform.column("V(in)").unwrap();
+        let source = waveform.column("V(V1)").unwrap();
+
+        assert!((sample_at(&waveform, 1e-6) - 1.).abs() < 1e-9);
+        let peak = 2. * (1. - (-3.5f64).exp());
+        assert!((sample_at(&waveform, 4.5e-6) - 1. - peak).abs() < 1e-9);
+
+        for (&t, (v, source)) in waveform.time.iter().zip(v.iter().zip(source)) {
+            assert!((v.re - 1. - source.re).abs() < 1e-9, "at {t}");
+        }
+
+        let last = waveform.time.len() - 1;
+        // rising for seven time constants and falling for seven of its own
+        assert!(source[last].re.abs() < 1e-9, "{}", source[last].re);
+
+        assert_eq!(
+            invalid_parameters(
+                "exp-source-1-terminal a V1=0 V2=1 TD1=-1u TAU1=1u TD2=-1u TAU2=-1u"
+            ),
+            ["TD1", "TD2", "TAU2"]
+        );
+        // falling before it rises
+        assert_eq!(
+            invalid_parameters("exp-source-1-terminal a V1=0 V2=1 TD1=2u TAU1=1u TD2=1u"),
+            ["TD2"]
+        );
+        // the fall takes its time constant from the rise
+        assert_eq!(
+            invalid_parameters("exp-source-1-terminal a V1=0 V2=1 TAU1=0"),
+            ["TAU1", "TAU2"]
+        );
+    }
+}
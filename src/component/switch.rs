@@ -0,0 +1,539 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    buffer::{Table, Tables},
+    component::{Component, FromParameters, Mode, Parameters, reached},
+    numerical::{LinearEquations, Tolerance, c64},
+};
+
+/// How closely a voltage-controlled switch locates the instant its control crosses
+/// the threshold, as a fraction of the step it crossed in.
+const CROSSING_RESOLUTION: f64 = 1e-3;
+
+/// A resistance of `resistance_ohm` from `n1` to `n2`.
+fn stamp_resistance(net: &mut LinearEquations, n1: u32, n2: u32, resistance_ohm: f64) {
+    let y = c64::real(1. / resistance_ohm);
+
+    net.add_a(n1, n1, y);
+    net.add_a(n1, n2, -y);
+    net.add_a(n2, n1, -y);
+    net.add_a(n2, n2, y);
+}
+
+/// Reports `Ron` and `Roff` as invalid unless they are positive.
+fn check_resistances(parameters: &mut Parameters, on_resistance_ohm: f64, off_resistance_ohm: f64) {
+    for (parameter, resistance_ohm) in [("Ron", on_resistance_ohm), ("Roff", off_resistance_ohm)] {
+        if resistance_ohm.is_nan() || resistance_ohm <= 0. {
+            parameters.invalid(parameter);
+        }
+    }
+}
+
+/// Switches between `Ron` and `Roff` as the voltage across `[.., .., in+, in-]`
+/// rises above `Vt + Vh` or falls below `Vt - Vh`. It only switches between time
+/// points, and has the transient driver put one where the control crosses over.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct VoltageControlledSwitch {
+    pub on_resistance_ohm: f64,
+    pub off_resistance_ohm: f64,
+    pub threshold_volt: f64,
+    pub hysteresis_volt: f64,
+}
+
+impl Default for VoltageControlledSwitch {
+    fn default() -> Self {
+        Self {
+            on_resistance_ohm: 1.,
+            off_resistance_ohm: 1e6,
+            threshold_volt: 0.,
+            hysteresis_volt: 0.,
+        }
+    }
+}
+
+impl FromParameters for VoltageControlledSwitch {
+    /// Both resistances have to be positive, and the hysteresis can't be negative.
+    fn from_parameters(parameters: &mut Parameters) -> Self {
+        let default = Self::default();
+
+        let switch = Self {
+            on_resistance_ohm: parameters.real_or("Ron", default.on_resistance_ohm),
+            off_resistance_ohm: parameters.real_or("Roff", default.off_resistance_ohm),
+            threshold_volt: parameters.real_or("Vt", default.threshold_volt),
+            hysteresis_volt: parameters.real_or("Vh", default.hysteresis_volt),
+        };
+
+        check_resistances(
+            parameters,
+            switch.on_resistance_ohm,
+            switch.off_resistance_ohm,
+        );
+
+        if switch.hysteresis_volt.is_nan() || switch.hysteresis_volt < 0. {
+            parameters.invalid("Vh");
+        }
+
+        switch
+    }
+}
+
+impl VoltageControlledSwitch {
+    /// The control voltage at which a switch that is `closed` or not flips over.
+    fn flips_at(&self, closed: bool) -> f64 {
+        if closed {
+            self.threshold_volt - self.hysteresis_volt
+        } else {
+            self.threshold_volt + self.hysteresis_volt
+        }
+    }
+
+    /// Whether a switch that is `closed` or not is closed with `v_control` across its control.
+    fn closes(&self, v_control: f64, closed: bool) -> bool {
+        if closed {
+            v_control >= self.flips_at(closed)
+        } else {
+            v_control > self.flips_at(closed)
+        }
+    }
+
+    fn resistance(&self, state: &SwitchState) -> f64 {
+        if state.closed() {
+            self.on_resistance_ohm
+        } else {
+            self.off_resistance_ohm
+        }
+    }
+}
+
+/// Whether the switch is closed and its control voltage at the last accepted time point.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct SwitchState {
+    closed: u64,
+    v_control: f64,
+}
+
+impl SwitchState {
+    pub fn closed(&self) -> bool {
+        self.closed != 0
+    }
+}
+
+impl Component for VoltageControlledSwitch {
+    type State = SwitchState;
+    const TERMINAL_COUNT: usize = 4;
+    const PRIORITY: usize = 10;
+    const PARAMETERS: &[&'static str] = &["Ron", "Roff", "Vt", "Vh", "V", "I", "P", "closed"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (0, 1), (1, 0), (1, 1)];
+    // the operating point has to find out whether it is closed by iterating
+    const NONLINEAR_AT_DC: bool = true;
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        _: Mode,
+        [n1, n2, _, _]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        stamp_resistance(net, n1, n2, self.resistance(state));
+    }
+
+    fn post_stamp(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        [_, _, ip, in_]: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+    ) {
+        if let Mode::Ac { .. } = mode {
+            return;
+        }
+
+        let v_control = net.get_voltage_across(ip, in_).re;
+        *state = SwitchState {
+            closed: self.closes(v_control, state.closed()) as u64,
+            v_control,
+        };
+    }
+
+    fn limit(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        terminals: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+        _: &Tolerance,
+    ) -> bool {
+        // only called for the operating point, a transient step keeps it up to the next time point
+        let closed = state.closed();
+        self.post_stamp(net, mode, terminals, state);
+        state.closed() != closed
+    }
+
+    fn breakpoint(
+        &self,
+        net: &LinearEquations,
+        mode: Mode,
+        [_, _, ip, in_]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) -> Option<f64> {
+        let Mode::Transient { dt, .. } = mode else {
+            return None;
+        };
+
+        let v_control = net.get_voltage_across(ip, in_).re;
+        if self.closes(v_control, state.closed()) == state.closed() {
+            return None;
+        }
+
+        // where the control crossed over, interpolating linearly along the step
+        let fraction =
+            (self.flips_at(state.closed()) - state.v_control) / (v_control - state.v_control);
+
+        (fraction > 0. && fraction < 1. - CROSSING_RESOLUTION).then_some(fraction * dt)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [n1, n2, _, _]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let r = c64::real(self.resistance(state));
+        let v = net.get_voltage_across(n1, n2);
+
+        match parameter {
+            "Ron" => Some(c64::real(self.on_resistance_ohm)),
+            "Roff" => Some(c64::real(self.off_resistance_ohm)),
+            "Vt" => Some(c64::real(self.threshold_volt)),
+            "Vh" => Some(c64::real(self.hysteresis_volt)),
+            "V" => Some(v),
+            "I" => Some(v / r),
+            "P" => Some(v * v / r),
+            "closed" => Some(c64::real(state.closed() as u8 as f64)),
+            _ => None,
+        }
+    }
+}
+
+/// Switches between `Roff` and `Ron` at the times `T1`, `T2` and so on, kept in the
+/// circuit's [`Tables`], starting open and flipping at each of them, with a time point
+/// exactly on every one.
+#[derive(Debug, Pod, Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct TimeControlledSwitch {
+    pub on_resistance_ohm: f64,
+    pub off_resistance_ohm: f64,
+    /// Times at which it flips.
+    pub schedule: Table,
+}
+
+impl TimeControlledSwitch {
+    /// Reads the schedule as `T1`, `T2` and so on up to the first missing time, which
+    /// can't be negative or go back. Both resistances have to be positive.
+    pub fn from_parameters(parameters: &mut Parameters, tables: &mut Tables) -> Self {
+        let default = VoltageControlledSwitch::default();
+        let mut schedule_s = vec![];
+        let mut previous_s = 0.;
+
+        let mut n = 1;
+        while let Some(time) = parameters.take(&format!("T{n}")) {
+            if time.re.is_nan() || time.re < previous_s {
+                parameters.invalid(&format!("T{n}"));
+            }
+
+            previous_s = time.re;
+            schedule_s.push(time.re);
+            n += 1;
+        }
+
+        let switch = Self {
+            on_resistance_ohm: parameters.real_or("Ron", default.on_resistance_ohm),
+            off_resistance_ohm: parameters.real_or("Roff", default.off_resistance_ohm),
+            schedule: tables.push(schedule_s),
+        };
+
+        check_resistances(
+            parameters,
+            switch.on_resistance_ohm,
+            switch.off_resistance_ohm,
+        );
+
+        switch
+    }
+
+    fn resistance(&self, state: &ScheduleState) -> f64 {
+        if state.closed() {
+            self.on_resistance_ohm
+        } else {
+            self.off_resistance_ohm
+        }
+    }
+}
+
+/// The time of the last accepted time point, with whether the switch is closed then
+/// and when it flips next, looked up in the schedule on [`Component::load`].
+#[derive(Debug, Pod, Zeroable, Clone, Copy, Default)]
+#[repr(C)]
+pub struct ScheduleState {
+    t: f64,
+    closed: u64,
+    next_s: f64,
+}
+
+impl ScheduleState {
+    pub fn closed(&self) -> bool {
+        self.closed != 0
+    }
+}
+
+impl Component for TimeControlledSwitch {
+    type State = ScheduleState;
+    const TERMINAL_COUNT: usize = 2;
+    const PRIORITY: usize = 10;
+    const PARAMETERS: &[&'static str] = &["Ron", "Roff", "V", "I", "P", "closed", "t"];
+    const ACTIVE_TERMINALS: &[(usize, usize)] = &[(0, 0), (0, 1), (1, 0), (1, 1)];
+
+    fn stamp(
+        &self,
+        net: &mut LinearEquations,
+        _: Mode,
+        [n1, n2]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) {
+        stamp_resistance(net, n1, n2, self.resistance(state));
+    }
+
+    fn post_stamp(
+        &self,
+        _: &LinearEquations,
+        mode: Mode,
+        _: [u32; Self::TERMINAL_COUNT],
+        state: &mut Self::State,
+    ) {
+        if let Mode::Transient { dt, .. } = mode {
+            state.t += dt;
+        }
+    }
+
+    fn load(&self, tables: &Tables, state: &mut Self::State) {
+        let schedule_s = tables.get(self.schedule);
+        let flips = schedule_s.iter().filter(|&&time| reached(time, state.t));
+
+        state.closed = (flips.count() % 2) as u64;
+        state.next_s = schedule_s
+            .iter()
+            .copied()
+            .filter(|&time| !reached(time, state.t))
+            .fold(f64::INFINITY, f64::min);
+    }
+
+    fn breakpoint(
+        &self,
+        _: &LinearEquations,
+        mode: Mode,
+        _: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+    ) -> Option<f64> {
+        let Mode::Transient { dt, .. } = mode else {
+            return None;
+        };
+
+        let until = state.next_s - state.t;
+        (until < dt).then_some(until)
+    }
+
+    fn parameter(
+        &self,
+        net: &LinearEquations,
+        [n1, n2]: [u32; Self::TERMINAL_COUNT],
+        state: &Self::State,
+        parameter: &str,
+    ) -> Option<c64> {
+        let r = c64::real(self.resistance(state));
+        let v = net.get_voltage_across(n1, n2);
+
+        match parameter {
+            "Ron" => Some(c64::real(self.on_resistance_ohm)),
+            "Roff" => Some(c64::real(self.off_resistance_ohm)),
+            "V" => Some(v),
+            "I" => Some(v / r),
+            "P" => Some(v * v / r),
+            "closed" => Some(c64::real(state.closed() as u8 as f64)),
+            "t" => Some(c64::real(state.t)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DcOperatingPoint, Probe, Timestep, Transient,
+        component::Integration,
+        numerical::Tolerance,
+        parser::{build, invalid_parameters},
+    };
+
+    #[test]
+    fn test_timed_switch() {
+        // charges the capacitor from 0.355ms to 0.755ms
+        let mut circuit = build(
+            r#"
+            dc-source-1-terminal            in          V=1
+            timed-switch            "S1"    in mid      T1=0.355m T2=0.755m Roff=1T
+            resistor                        mid out     R=1k
+            capacitor                       out gnd     C=100n
+            ground                          gnd
+            "#,
+        );
+
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: 1e-3,
+            step_s: 10e-6,
+            timestep: Timestep::Fixed,
+            integration: Integration::BackwardEuler,
+            probes: vec![Probe::voltage("out"), Probe::parameter("S1", "closed")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+        let time = &waveform.time;
+        let v = waveform.column("V(out)").unwrap();
+        let closed = waveform.column("closed(S1)").unwrap();
+
+        let on = time
+            .iter()
+            .position(|&t| (t - 0.355e-3).abs() < 1e-15)
+            .unwrap();
+        let off = time
+            .iter()
+            .position(|&t| (t - 0.755e-3).abs() < 1e-15)
+            .unwrap();
+
+        assert!(v[on].re.abs() < 1e-6, "{}", v[on].re);
+        assert_eq!(closed[on - 1].re, 0.);
+        assert_eq!(closed[on].re, 1.);
+        assert_eq!(closed[off].re, 0.);
+
+        // four time constants, then held
+        let expected = 1. - (-4f64).exp();
+        assert!((v[off].re - expected).abs() < 1e-2, "{}", v[off].re);
+        assert!((v.last().unwrap().re - v[off].re).abs() < 1e-6);
+
+        assert_eq!(
+            invalid_parameters("timed-switch a b T1=-1m T2=2m T3=1m Ron=0"),
+            ["T1", "T3", "Ron"]
+        );
+    }
+
+    #[test]
+    fn test_voltage_controlled_switch() {
+        let netlist = r#"
+            ac-source-1-terminal            ctl         V=1 f=1k
+            dc-source-1-terminal            in          V=1
+            switch                  "S1"    in out ctl gnd  Vt=0.25 Vh=0.25 Ron=1 Roff=1G
+            resistor                "R1"    out gnd     R=1k
+            ground                          gnd
+        "#;
+
+        let mut circuit = build(netlist);
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: 1e-3,
+            step_s: 10e-6,
+            timestep: Timestep::Fixed,
+            integration: Integration::BackwardEuler,
+            probes: vec![Probe::voltage("out"), Probe::parameter("S1", "closed")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+        let time = &waveform.time;
+        let closed = waveform.column("closed(S1)").unwrap();
+
+        // starts open, closes on the cosine's peak, opens as it falls through 0 and
+        // closes again as it rises through 0.5
+        assert_eq!(closed[0].re, 0.);
+        assert_eq!(closed[1].re, 1.);
+        let off = 1 + closed[1..].iter().position(|z| z.re == 0.).unwrap();
+        let on = off + closed[off..].iter().position(|z| z.re == 1.).unwrap();
+
+        assert!((time[off] - 0.25e-3).abs() < 2e-8, "{}", time[off]);
+        assert!((time[on] - 1e-3 * 5. / 6.).abs() < 2e-8, "{}", time[on]);
+
+        let v = waveform.column("V(out)").unwrap();
+        assert!((v[off].re - 1e3 / 1001.).abs() < 1e-9);
+        assert!(v[off + 1].re < 1e-5);
+        assert!((v[on + 1].re - 1e3 / 1001.).abs() < 1e-9);
+
+        // the operating point iterates to find it closed by a DC control
+        let netlist = netlist.replace("ac-source-1-terminal", "dc-source-1-terminal");
+        let mut circuit = build(&netlist.replace("f=1k", ""));
+        let op = DcOperatingPoint.run(&mut circuit).unwrap();
+        assert_eq!(op.parameter("S1", "closed").unwrap().re, 1.);
+        assert!((op.voltage("out").unwrap().re - 1e3 / 1001.).abs() < 1e-9);
+
+        // a negative hysteresis would have it flip back and forth without end
+        assert_eq!(
+            invalid_parameters("switch a b c d Ron=-1 Roff=0 Vh=-0.1"),
+            ["Ron", "Roff", "Vh"]
+        );
+    }
+
+    #[test]
+    fn test_timed_switch_schedule() {
+        // more flips than fit a fixed array, each 1ns past where the steps would land
+        let schedule_s: Vec<_> = (1..=10)
+            .map(|n| n as f64 * 1e-3 + 1e-4 + (n - 1) as f64 * 1e-9)
+            .collect();
+        let schedule: Vec<_> = schedule_s
+            .iter()
+            .enumerate()
+            .map(|(i, time)| format!("T{}={time}", i + 1))
+            .collect();
+        let mut circuit = build(&format!(
+            r#"
+            dc-source-1-terminal            in          V=1
+            resistor                        in out      R=1k
+            capacitor                       out gnd     C=1u
+            timed-switch            "S1"    in load     {}
+            resistor                        load gnd    R=1k
+            ground                          gnd
+            "#,
+            schedule.join(" ")
+        ));
+
+        let transient = Transient {
+            start_s: 0.,
+            stop_s: 12e-3,
+            step_s: 1e-6,
+            timestep: Timestep::Adaptive {
+                min_step_s: 1e-12,
+                max_step_s: 1e-4,
+                tolerance: Tolerance::default(),
+            },
+            integration: Integration::Trapezoidal,
+            probes: vec![Probe::parameter("S1", "closed")],
+        };
+
+        let waveform = transient.run(&mut circuit).unwrap();
+        let time = &waveform.time;
+        let closed = waveform.column("closed(S1)").unwrap();
+        let flips: Vec<_> = closed
+            .windows(2)
+            .zip(&time[1..])
+            .filter(|(closed, _)| closed[0] != closed[1])
+            .map(|(_, &t)| t)
+            .collect();
+
+        assert_eq!(flips.len(), schedule_s.len());
+        for (t, time) in flips.iter().zip(&schedule_s) {
+            assert!((t - time).abs() < 1e-15, "{} != {}", t, time);
+        }
+
+        // the steps go back to what they were after the 1ns ones cut short for the flips
+        assert!(time.len() < 160, "{}", time.len());
+    }
+}
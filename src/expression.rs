@@ -1,6 +1,6 @@
 use std::fmt::{Debug, Display, Formatter};
 
-use crate::numerical::c64;
+use crate::{numerical::c64, si::parse_si_number};
 
 #[derive(Debug, Clone)]
 pub enum ExpressionError {
@@ -59,28 +59,212 @@ pub enum Expression {
 
 impl Expression {
     pub fn compute_fixed(&self) -> Option<c64> {
+        self.evaluate(&|_, _| None)
+    }
+
+    /// Computes the value, taking variables by name and subscript from `variable`.
+    pub fn evaluate(&self, variable: &dyn Fn(&str, Option<&str>) -> Option<c64>) -> Option<c64> {
         Some(match self {
             Expression::Imaginary(im) => c64::new(0., *im),
             Expression::Real(re) => c64::new(*re, 0.),
-            Expression::Variable { name, subscript } => return None,
+            Expression::Variable { name, subscript } => variable(name, subscript.as_deref())?,
             Expression::Binop { op, lhs, rhs } => {
-                let (lhs, rhs) = (lhs.compute_fixed()?, rhs.compute_fixed()?);
+                let (lhs, rhs) = (lhs.evaluate(variable)?, rhs.evaluate(variable)?);
 
                 match op {
                     BinaryOperator::Add => lhs + rhs,
                     BinaryOperator::Subtract => lhs - rhs,
                     BinaryOperator::Multiply => lhs * rhs,
                     BinaryOperator::Divide => lhs / rhs,
-                    BinaryOperator::Exponentiate => todo!(),
-                    BinaryOperator::Phase => todo!(),
+                    BinaryOperator::Exponentiate => lhs.powc(rhs),
+                    BinaryOperator::Phase => c64::polar(lhs.norm(), rhs.re.to_radians()),
+                }
+            }
+            Expression::Bracketed(expression) => expression.evaluate(variable)?,
+            Expression::Function { name, arguments } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(variable))
+                    .collect::<Option<Vec<_>>>()?;
+
+                apply_function(name, &arguments)?
+            }
+        })
+    }
+
+    /// Every variable it references by name and subscript, once each in order of appearance.
+    pub fn variables(&self) -> Vec<(&str, Option<&str>)> {
+        let mut variables = vec![];
+        self.collect_variables(&mut variables);
+        variables
+    }
+
+    fn collect_variables<'a>(&'a self, variables: &mut Vec<(&'a str, Option<&'a str>)>) {
+        match self {
+            Expression::Imaginary(_) | Expression::Real(_) => {}
+            Expression::Variable { name, subscript } => {
+                let variable = (name.as_str(), subscript.as_deref());
+                if !variables.contains(&variable) {
+                    variables.push(variable);
+                }
+            }
+            Expression::Binop { lhs, rhs, .. } => {
+                lhs.collect_variables(variables);
+                rhs.collect_variables(variables);
+            }
+            Expression::Bracketed(expression) => expression.collect_variables(variables),
+            Expression::Function { arguments, .. } => {
+                for argument in arguments {
+                    argument.collect_variables(variables);
+                }
+            }
+        }
+    }
+
+    /// The first function it calls that doesn't exist or doesn't take that many
+    /// arguments, by name and number of arguments.
+    pub fn unknown_function(&self) -> Option<(&str, usize)> {
+        match self {
+            Expression::Imaginary(_) | Expression::Real(_) | Expression::Variable { .. } => None,
+            Expression::Binop { lhs, rhs, .. } => {
+                lhs.unknown_function().or_else(|| rhs.unknown_function())
+            }
+            Expression::Bracketed(expression) => expression.unknown_function(),
+            Expression::Function { name, arguments } => {
+                // every function there is can be applied to ones
+                let ones = vec![c64::ONE; arguments.len()];
+                if apply_function(name, &ones).is_none() {
+                    return Some((name, arguments.len()));
+                }
+
+                arguments.iter().find_map(Expression::unknown_function)
+            }
+        }
+    }
+
+    /// The derivative by the variable `name` with `subscript`, or `None` if it goes
+    /// through an operator or function that isn't differentiated symbolically.
+    pub fn derivative(&self, name: &str, subscript: Option<&str>) -> Option<Expression> {
+        use BinaryOperator::*;
+        use Expression::*;
+
+        let d = |expression: &Expression| expression.derivative(name, subscript);
+
+        Some(match self {
+            Imaginary(_) | Real(_) => Real(0.),
+            Variable {
+                name: variable,
+                subscript: variable_subscript,
+            } => {
+                let matches = variable == name && variable_subscript.as_deref() == subscript;
+                Real(if matches { 1. } else { 0. })
+            }
+            Bracketed(expression) => d(expression)?,
+            Binop { op, lhs, rhs } => {
+                let (u, v) = (*lhs.clone(), *rhs.clone());
+                let (du, dv) = (d(lhs)?, d(rhs)?);
+
+                match op {
+                    Add => binop(Add, du, dv),
+                    Subtract => binop(Subtract, du, dv),
+                    Multiply => binop(Add, binop(Multiply, du, v), binop(Multiply, u, dv)),
+                    Divide => binop(
+                        Divide,
+                        binop(
+                            Subtract,
+                            binop(Multiply, du, v.clone()),
+                            binop(Multiply, u, dv),
+                        ),
+                        binop(Multiply, v.clone(), v),
+                    ),
+                    // a constant exponent keeps clear of the logarithm of the base
+                    Exponentiate if dv == Real(0.) => binop(
+                        Multiply,
+                        binop(
+                            Multiply,
+                            v.clone(),
+                            binop(Exponentiate, u, binop(Subtract, v, Real(1.))),
+                        ),
+                        du,
+                    ),
+                    Exponentiate => binop(
+                        Multiply,
+                        self.clone(),
+                        binop(
+                            Add,
+                            binop(Multiply, dv, function("ln", u.clone())),
+                            binop(Divide, binop(Multiply, v, du), u),
+                        ),
+                    ),
+                    Phase => return None,
                 }
             }
-            Expression::Bracketed(expression) => expression.compute_fixed()?,
-            Expression::Function { name, arguments } => todo!(),
+            Function {
+                name: function_name,
+                arguments,
+            } => {
+                let [u] = &arguments[..] else {
+                    return None;
+                };
+
+                let outer = match function_name.as_str() {
+                    "exp" => self.clone(),
+                    "ln" => binop(Divide, Real(1.), u.clone()),
+                    "sqrt" => binop(Divide, Real(0.5), self.clone()),
+                    "sin" => function("cos", u.clone()),
+                    "cos" => binop(Subtract, Real(0.), function("sin", u.clone())),
+                    _ => return None,
+                };
+
+                binop(Multiply, outer, d(u)?)
+            }
         })
     }
 }
 
+/// `lhs op rhs`, leaving out additions of zero and multiplications by zero or one.
+fn binop(op: BinaryOperator, lhs: Expression, rhs: Expression) -> Expression {
+    let zero = Expression::Real(0.);
+    let one = Expression::Real(1.);
+
+    match op {
+        BinaryOperator::Add if lhs == zero => rhs,
+        BinaryOperator::Add | BinaryOperator::Subtract if rhs == zero => lhs,
+        BinaryOperator::Multiply if lhs == zero || rhs == zero => zero,
+        BinaryOperator::Divide if lhs == zero => zero,
+        BinaryOperator::Multiply if lhs == one => rhs,
+        BinaryOperator::Multiply if rhs == one => lhs,
+        _ => Expression::Binop {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+    }
+}
+
+fn function(name: &str, argument: Expression) -> Expression {
+    Expression::Function {
+        name: name.to_string(),
+        arguments: vec![argument],
+    }
+}
+
+fn apply_function(name: &str, arguments: &[c64]) -> Option<c64> {
+    Some(match (name, arguments) {
+        ("exp", &[z]) => z.exp(),
+        ("sin", &[z]) => z.sin(),
+        ("cos", &[z]) => z.cos(),
+        ("ln", &[z]) => z.ln(),
+        ("sqrt", &[z]) => z.sqrt(),
+        ("abs", &[z]) => c64::real(z.norm()),
+        ("arg", &[z]) => c64::real(z.arg()),
+        ("re", &[z]) => c64::real(z.re),
+        ("im", &[z]) => c64::real(z.im),
+        ("conj", &[z]) => z.conj(),
+        _ => return None,
+    })
+}
+
 impl Display for Expression {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -143,9 +327,10 @@ fn take_operand(input: &str) -> ExpressionResult<(Expression, &str)> {
         return Ok((Expression::Bracketed(Box::new(expr)), rest));
     }
 
+    // dots belong to decimals as well as to names, like the net in `V_a.b`
     let mut i = 0;
     while i < bytes.len()
-        && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] > 127)
+        && (bytes[i].is_ascii_alphanumeric() || b"_.".contains(&bytes[i]) || bytes[i] > 127)
     {
         i += 1;
     }
@@ -196,7 +381,7 @@ fn take_operand(input: &str) -> ExpressionResult<(Expression, &str)> {
         ));
     }
 
-    if let Ok(value) = token.parse::<f64>() {
+    if let Some(value) = parse_si_number(token) {
         return Ok((Expression::Real(value), rest));
     }
 
@@ -224,7 +409,7 @@ fn take_operand(input: &str) -> ExpressionResult<(Expression, &str)> {
     ))
 }
 
-const OPERATORS: [(&'static str, BinaryOperator); 8] = [
+const OPERATORS: [(&str, BinaryOperator); 8] = [
     ("<", BinaryOperator::Phase),
     ("∠", BinaryOperator::Phase),
     ("**", BinaryOperator::Exponentiate),
@@ -239,8 +424,7 @@ fn take_operator(input: &str) -> ExpressionResult<(BinaryOperator, &str)> {
     let input = input.trim_start();
 
     for (symbol, op) in OPERATORS {
-        if input.starts_with(symbol) {
-            let rest = &input[symbol.len()..];
+        if let Some(rest) = input.strip_prefix(symbol) {
             return Ok((op, rest));
         }
     }
@@ -252,16 +436,8 @@ fn take_binop(input: &str) -> ExpressionResult<(Expression, &str)> {
     let (mut lhs, mut rest) = take_operand(input)?;
     rest = take_whitespace(rest);
 
-    loop {
-        let op = match take_operator(rest) {
-            Ok((op, r)) => {
-                rest = r;
-                op
-            }
-            Err(_) => break,
-        };
-
-        rest = take_whitespace(rest);
+    while let Ok((op, r)) = take_operator(rest) {
+        rest = take_whitespace(r);
 
         let (rhs, r) = take_operand(rest)?;
         rest = r;
@@ -319,19 +495,18 @@ fn reorder_binop(op: BinaryOperator, lhs: Expression, rhs: Expression) -> Expres
             lhs: rlhs,
             rhs: rrhs,
         } = right.clone()
+            && precedence(rop) < precedence(root_op)
         {
-            if precedence(rop) < precedence(root_op) {
-                right = *rlhs;
-                let new_left = Binop {
-                    op: root_op,
-                    lhs: Box::new(left),
-                    rhs: Box::new(right),
-                };
-                left = new_left;
-                root_op = rop;
-                right = *rrhs;
-                continue;
-            }
+            right = *rlhs;
+            let new_left = Binop {
+                op: root_op,
+                lhs: Box::new(left),
+                rhs: Box::new(right),
+            };
+            left = new_left;
+            root_op = rop;
+            right = *rrhs;
+            continue;
         }
 
         if let Binop {
@@ -339,19 +514,18 @@ fn reorder_binop(op: BinaryOperator, lhs: Expression, rhs: Expression) -> Expres
             lhs: llhs,
             rhs: lrhs,
         } = left.clone()
+            && precedence(lop) < precedence(root_op)
         {
-            if precedence(lop) < precedence(root_op) {
-                left = *lrhs;
-                let new_right = Binop {
-                    op: root_op,
-                    lhs: Box::new(left),
-                    rhs: Box::new(right),
-                };
-                root_op = lop;
-                right = new_right;
-                left = *llhs;
-                continue;
-            }
+            left = *lrhs;
+            let new_right = Binop {
+                op: root_op,
+                lhs: Box::new(left),
+                rhs: Box::new(right),
+            };
+            root_op = lop;
+            right = new_right;
+            left = *llhs;
+            continue;
         }
 
         break;
@@ -386,4 +560,70 @@ mod test {
         println!("{:?}", taken);
         assert_eq!(rest, "this is the rest")
     }
+
+    #[test]
+    fn test_numbers() {
+        let (taken, _) = parse_expr("0.5 * 2k").unwrap();
+        assert_eq!(taken.compute_fixed(), Some(c64::real(1e3)));
+    }
+
+    #[test]
+    fn test_dotted_names() {
+        let (expression, rest) = parse_expr("V_a.b * 1.5 rest").unwrap();
+        assert_eq!(expression.variables(), [("V", Some("a.b"))]);
+        assert_eq!(rest, "rest");
+
+        let at = |_: &str, _: Option<&str>| Some(c64::real(2.));
+        assert_eq!(expression.evaluate(&at), Some(c64::real(3.)));
+    }
+
+    #[test]
+    fn test_compute_operators_and_functions() {
+        let value = |input: &str| parse_expr(input).unwrap().0.compute_fixed().unwrap();
+        let close =
+            |z: c64, re: f64, im: f64| (z.re - re).abs() < 1e-12 && (z.im - im).abs() < 1e-12;
+
+        assert!(close(value("2 ^ 10"), 1024., 0.));
+        assert!(close(value("3 ** 2 * 2"), 18., 0.));
+        // magnitude and phase in degrees
+        assert!(close(value("2 < 90"), 0., 2.));
+        assert!(close(value("(3 + 4j) < 180"), -5., 0.));
+        assert!(close(value("sqrt(0 - 4)"), 0., 2.));
+        assert!(close(value("abs(3 + 4j)"), 5., 0.));
+        assert!(close(value("re(exp(ln(2)))"), 2., 0.));
+        assert!(close(value("conj(1 + 1j)"), 1., -1.));
+        assert_eq!(parse_expr("nope(1)").unwrap().0.compute_fixed(), None);
+    }
+
+    #[test]
+    fn test_derivative() {
+        let (expression, _) = parse_expr("V_a * V_b ^ 2 + exp(V_a) / V_b").unwrap();
+        assert_eq!(expression.variables(), [("V", Some("a")), ("V", Some("b"))]);
+
+        let at = |name: &str, subscript: Option<&str>| match (name, subscript) {
+            ("V", Some("a")) => Some(c64::real(0.5)),
+            ("V", Some("b")) => Some(c64::real(2.)),
+            _ => None,
+        };
+
+        let (a, b) = (0.5f64, 2f64);
+        let by_a = expression.derivative("V", Some("a")).unwrap();
+        let by_b = expression.derivative("V", Some("b")).unwrap();
+        let expected_a = b * b + a.exp() / b;
+        let expected_b = 2. * a * b - a.exp() / (b * b);
+
+        assert!((by_a.evaluate(&at).unwrap().re - expected_a).abs() < 1e-12);
+        assert!((by_b.evaluate(&at).unwrap().re - expected_b).abs() < 1e-12);
+        assert_eq!(
+            expression.derivative("t", None).unwrap(),
+            Expression::Real(0.)
+        );
+        assert!(
+            parse_expr("abs(t)")
+                .unwrap()
+                .0
+                .derivative("t", None)
+                .is_none()
+        );
+    }
 }
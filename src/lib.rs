@@ -1,5 +1,6 @@
 #![feature(generic_const_exprs)]
 
+mod analysis;
 mod buffer;
 mod circuit;
 mod component;
@@ -9,6 +10,7 @@ mod parser;
 mod printing;
 mod si;
 
+pub use analysis::*;
 pub use buffer::*;
 pub use circuit::*;
 pub use component::*;
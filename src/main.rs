@@ -1,37 +1,36 @@
 #![feature(generic_const_exprs)]
 
-use electrocute::Parser;
-use wasm_bindgen::prelude::*;
-
-use crate::{
-    component::{ComponentLibrary, DC1Source, Ground, Resistor},
-    parser::{CircuitBuilder},
-};
-
-mod buffer;
-mod circuit;
-mod component;
-mod expression;
-mod numerical;
-mod parser;
-mod printing;
-mod si;
-
+use electrocute::{CircuitBuilder, ComponentLibrary, DcOperatingPoint, Parser};
 
 #[cfg(not(target_arch = "wasm32"))]
 pub fn main() {
-    let mut components = ComponentLibrary::new();
-
-    components
-        .register_component::<DC1Source>("dc-source-1-terminal", |_| todo!())
-        .register_component::<Resistor>("resistor", |_| todo!())
-        .register_component::<Ground>("ground", |_| todo!());
+    let components = ComponentLibrary::with_builtins();
 
     let netlist = include_str!("../sample.netlist");
 
     let mut p = Parser::from(netlist);
 
-    let c = p.parse_commands();
-    
-    println!("{:#?}", c);
+    let commands = match p.parse_commands() {
+        Ok(commands) => commands,
+        Err(err) => {
+            eprintln!("{:?}", err);
+            return;
+        }
+    };
+
+    let mut builder = CircuitBuilder::new();
+    builder.add_commands(commands);
+
+    let mut circuit = match builder.build(&components) {
+        Ok(circuit) => circuit,
+        Err(errors) => {
+            eprintln!("{:#?}", errors);
+            return;
+        }
+    };
+
+    match DcOperatingPoint.run(&mut circuit) {
+        Ok(op) => println!("{}", op.table()),
+        Err(err) => eprintln!("{:?}", err),
+    }
 }
@@ -67,6 +67,36 @@ impl c64 {
             im: ea * self.im.sin(),
         }
     }
+
+    pub fn powc(self, exponent: Self) -> Self {
+        if self == Self::ZERO {
+            return if exponent == Self::ZERO {
+                Self::ONE
+            } else {
+                Self::ZERO
+            };
+        }
+
+        (exponent * self.ln()).exp()
+    }
+
+    pub fn sqrt(self) -> Self {
+        Self::polar(self.norm().sqrt(), self.arg() / 2.)
+    }
+
+    pub fn sin(self) -> Self {
+        Self {
+            re: self.re.sin() * self.im.cosh(),
+            im: self.re.cos() * self.im.sinh(),
+        }
+    }
+
+    pub fn cos(self) -> Self {
+        Self {
+            re: self.re.cos() * self.im.cosh(),
+            im: -self.re.sin() * self.im.sinh(),
+        }
+    }
 }
 
 impl Add for c64 {
@@ -141,3 +171,21 @@ impl Debug for c64 {
         f.write_str(&format_complex_si(*self))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_powers() {
+        let close = |a: c64, b: c64| (a - b).norm() < 1e-12;
+
+        assert!(close(c64::real(2.).powc(c64::real(3.)), c64::real(8.)));
+        assert!(close(c64::imag(1.).powc(c64::real(2.)), c64::real(-1.)));
+        assert_eq!(c64::ZERO.powc(c64::ZERO), c64::ONE);
+        assert_eq!(c64::ZERO.powc(c64::real(2.)), c64::ZERO);
+
+        assert!(close(c64::real(-4.).sqrt(), c64::imag(2.)));
+        assert!(close(c64::imag(2.).sqrt(), c64::new(1., 1.)));
+    }
+}
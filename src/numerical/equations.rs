@@ -1,6 +1,6 @@
 use std::collections::{BTreeMap, HashMap};
 
-use crate::numerical::{complex::c64, solve};
+use crate::numerical::{SingularMatrix, complex::c64, solve, solve_direct};
 
 // CSR
 #[derive(Debug, Clone)]
@@ -73,8 +73,12 @@ impl LinearEquations {
         for (i, mut js) in compressed {
             max_row = max_row.max(i);
             js.sort_unstable();
+            js.dedup();
 
-            row_pointers.push(nnz as u32);
+            // rows without any entries still need their (empty) span
+            while row_pointers.len() <= i as usize {
+                row_pointers.push(nnz);
+            }
 
             for j in js {
                 max_col = max_col.max(j);
@@ -106,7 +110,7 @@ impl LinearEquations {
             .map(|(&k, &v)| (k, self.a[v]))
             .collect::<HashMap<_, _>>();
 
-        let coordinates = existing.keys().copied().chain(coordinates.into_iter());
+        let coordinates = existing.keys().copied().chain(coordinates);
 
         let mut new_self = LinearEquations::from_coordinates(coordinates);
 
@@ -117,7 +121,7 @@ impl LinearEquations {
         *self = new_self;
     }
 
-    fn dimensions(&self) -> (usize, usize) {
+    pub fn dimensions(&self) -> (usize, usize) {
         (self.b.len(), self.x.len())
     }
 
@@ -134,6 +138,23 @@ impl LinearEquations {
         );
     }
 
+    pub fn solve_direct(&mut self) -> Result<(), SingularMatrix> {
+        self.x = solve_direct(
+            &self.a[..],
+            &self.column_indices[..],
+            &self.row_pointers,
+            &self.b,
+        )?;
+
+        Ok(())
+    }
+
+    /// Zeroes `a` and `b` for restamping, `x` is kept as the previous solution.
+    pub fn clear(&mut self) {
+        self.a.fill(c64::ZERO);
+        self.b.fill(c64::ZERO);
+    }
+
     pub fn clear_row(&mut self, i: u32) {
         let row = i as usize;
         let start = self.row_pointers[row] as usize;
@@ -160,6 +181,13 @@ impl LinearEquations {
         self.b[i as usize] += value;
     }
 
+    /// Puts a conductance `g` from each of `nodes` to ground.
+    pub fn stamp_shunt(&mut self, nodes: impl IntoIterator<Item = u32>, g: c64) {
+        for node in nodes {
+            self.add_a(node, node, g);
+        }
+    }
+
     pub fn get_voltage_across(&self, from: u32, to: u32) -> c64 {
         self.x[from as usize] - self.x[to as usize]
     }
@@ -167,12 +195,37 @@ impl LinearEquations {
     pub fn get_current(&self, i: u32) -> c64 {
         self.b[i as usize]
     }
+
+    /// Solved current of the branch unknown `k`, flowing into the positive terminal
+    /// and through the element.
+    pub fn get_branch_current(&self, k: u32) -> c64 {
+        self.x[k as usize]
+    }
+
+    /// Stamps an ideal voltage source `v(positive) - v(negative) = voltage`
+    /// whose current is the branch unknown `k`. Without a negative terminal
+    /// the source is referenced to ground.
+    pub fn stamp_voltage_source(
+        &mut self,
+        positive: u32,
+        negative: Option<u32>,
+        k: u32,
+        voltage: c64,
+    ) {
+        self.add_a(positive, k, c64::ONE);
+        self.add_a(k, positive, c64::ONE);
+
+        if let Some(negative) = negative {
+            self.add_a(negative, k, -c64::ONE);
+            self.add_a(k, negative, -c64::ONE);
+        }
+
+        self.add_b(k, voltage);
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::f64::EPSILON;
-
     use super::*;
 
     #[test]
@@ -261,7 +314,7 @@ mod tests {
         le.set_b(0, c64::new(4.0, 0.0));
         le.set_b(1, c64::new(9.0, 0.0));
         le.solve();
-        assert!(le.x[0].re - 2.0 < EPSILON);
-        assert!(le.x[1].re - 3.0 < EPSILON);
+        assert!((le.x[0].re - 2.0).abs() < 1e-12);
+        assert!((le.x[1].re - 3.0).abs() < 1e-12);
     }
 }
@@ -1,7 +1,9 @@
 mod complex;
 mod equations;
 mod solve;
+mod tolerance;
 
 pub use complex::*;
 pub use equations::*;
 pub use solve::*;
+pub use tolerance::*;
@@ -65,26 +65,6 @@ fn vec_add_in_place(a: &mut [c64], b: &[c64]) {
     }
 }
 
-#[inline]
-fn diag(values: &[c64], row_pointers: &[u32], column_indices: &[u32]) -> impl Iterator<Item = c64> {
-    row_pointers
-        .array_windows()
-        .enumerate()
-        .filter_map(|(row, &[start, end])| {
-            let start = start as usize;
-            let end = end as usize;
-
-            column_indices[start..end]
-                .iter()
-                .zip(&values[start..end])
-                .find_map(
-                    |(&col, &val)| {
-                        if col as usize == row { Some(val) } else { None }
-                    },
-                )
-        })
-}
-
 // BiCGSTAB
 pub fn solve(
     values: &[c64],
@@ -95,8 +75,8 @@ pub fn solve(
     max_iters: u32,
     tol: f64,
 ) -> Vec<c64> {
-    let a_x0 = sparse_matmul(&values, &column_indices, &row_pointers, &x);
-    let mut r = vec_sub(&b, &a_x0);
+    let a_x0 = sparse_matmul(values, column_indices, row_pointers, &x);
+    let mut r = vec_sub(b, &a_x0);
 
     let r_hat = r.clone();
 
@@ -107,7 +87,7 @@ pub fn solve(
     let small = 1e-30f64;
 
     for _iter in 0..max_iters {
-        let a_p = sparse_matmul(&values, &column_indices, &row_pointers, &p);
+        let a_p = sparse_matmul(values, column_indices, row_pointers, &p);
 
         let denom_alpha = vec_dot(&r_hat, &a_p);
         if denom_alpha.norm() < small {
@@ -124,7 +104,7 @@ pub fn solve(
             break;
         }
 
-        let a_s = sparse_matmul(&values, &column_indices, &row_pointers, &s);
+        let a_s = sparse_matmul(values, column_indices, row_pointers, &s);
 
         let denom_omega = vec_dot(&a_s, &a_s);
         if denom_omega.norm() < small {
@@ -163,10 +143,170 @@ pub fn solve(
     x
 }
 
+/// Pivot magnitude below which the matrix is considered singular.
+const SINGULAR_PIVOT: f64 = 1e-30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SingularMatrix {
+    pub column: usize,
+}
+
+/// How large a pivot has to be against the largest entry of its column. Smaller
+/// thresholds favour sparse pivot rows over numerical stability.
+const PIVOT_THRESHOLD: f64 = 0.1;
+
+/// A sparse row, sorted by column.
+type SparseRow = Vec<(u32, c64)>;
+
+// Sparse LU with threshold partial pivoting, for the indefinite MNA systems
+// that BiCGSTAB breaks down on. Columns are eliminated in order, each by the
+// sparsest row holding a large enough entry of it, which keeps the fill-in low.
+pub fn solve_direct(
+    values: &[c64],
+    column_indices: &[u32],
+    row_pointers: &[u32],
+    b: &[c64],
+) -> Result<Vec<c64>, SingularMatrix> {
+    let n = b.len();
+
+    let mut rows: Vec<SparseRow> = row_pointers
+        .array_windows()
+        .map(|&[start, end]| {
+            (start as usize..end as usize)
+                .map(|k| (column_indices[k], values[k]))
+                .collect()
+        })
+        .collect();
+    rows.resize(n, vec![]);
+
+    let mut rhs = b.to_vec();
+
+    // the rows with an entry in each column, fill-in included
+    let mut column_rows = vec![vec![]; n];
+    for (i, row) in rows.iter().enumerate() {
+        for &(j, _) in row {
+            column_rows[j as usize].push(i);
+        }
+    }
+
+    let mut pivoted = vec![false; n];
+    let mut pivot_rows = Vec::with_capacity(n);
+
+    for col in 0..n {
+        let candidates: Vec<(usize, c64)> = column_rows[col]
+            .iter()
+            .filter(|&&i| !pivoted[i])
+            .filter_map(|&i| Some((i, entry(&rows[i], col as u32)?)))
+            .collect();
+
+        if candidates.iter().any(|(_, v)| !v.norm().is_finite()) {
+            return Err(SingularMatrix { column: col });
+        }
+
+        let largest = candidates.iter().map(|(_, v)| v.norm()).fold(0., f64::max);
+        if largest < SINGULAR_PIVOT {
+            return Err(SingularMatrix { column: col });
+        }
+
+        let (pivot_row, pivot) = candidates
+            .iter()
+            .filter(|(_, v)| v.norm() >= PIVOT_THRESHOLD * largest)
+            .min_by_key(|(i, _)| rows[*i].len())
+            .copied()
+            .unwrap();
+
+        pivoted[pivot_row] = true;
+        pivot_rows.push(pivot_row);
+
+        for &(i, v) in &candidates {
+            if i == pivot_row || v == c64::ZERO {
+                continue;
+            }
+
+            let factor = v / pivot;
+            let (row, filled) = eliminate(&rows[i], &rows[pivot_row], factor, col as u32);
+            rows[i] = row;
+            rhs[i] = rhs[i] - factor * rhs[pivot_row];
+
+            for j in filled {
+                column_rows[j as usize].push(i);
+            }
+        }
+    }
+
+    // every pivot row only holds its own column and later ones
+    let mut x = vec![c64::ZERO; n];
+
+    for col in (0..n).rev() {
+        let row = pivot_rows[col];
+        let mut acc = rhs[row];
+        let mut diagonal = c64::ZERO;
+
+        for &(j, v) in &rows[row] {
+            match (j as usize).cmp(&col) {
+                std::cmp::Ordering::Equal => diagonal = v,
+                std::cmp::Ordering::Greater => acc = acc - v * x[j as usize],
+                std::cmp::Ordering::Less => {}
+            }
+        }
+
+        x[col] = acc / diagonal;
+    }
+
+    Ok(x)
+}
+
+fn entry(row: &[(u32, c64)], column: u32) -> Option<c64> {
+    let k = row.binary_search_by_key(&column, |&(j, _)| j).ok()?;
+    Some(row[k].1)
+}
+
+/// `row - factor * pivot` without the eliminated `column`, and the columns this
+/// adds to `row`.
+fn eliminate(
+    row: &[(u32, c64)],
+    pivot: &[(u32, c64)],
+    factor: c64,
+    column: u32,
+) -> (SparseRow, Vec<u32>) {
+    let mut merged = Vec::with_capacity(row.len() + pivot.len());
+    let mut filled = vec![];
+    let (mut r, mut p) = (row.iter().peekable(), pivot.iter().peekable());
+
+    loop {
+        let (j, v) = match (r.peek(), p.peek()) {
+            (Some(&&(rj, rv)), Some(&&(pj, pv))) if rj == pj => {
+                r.next();
+                p.next();
+                (rj, rv - factor * pv)
+            }
+            (Some(&&(rj, rv)), Some(&&(pj, _))) if rj < pj => {
+                r.next();
+                (rj, rv)
+            }
+            (_, Some(&&(pj, pv))) => {
+                p.next();
+                filled.push(pj);
+                (pj, -(factor * pv))
+            }
+            (Some(&&(rj, rv)), None) => {
+                r.next();
+                (rj, rv)
+            }
+            (None, None) => break,
+        };
+
+        if j != column {
+            merged.push((j, v));
+        }
+    }
+
+    filled.retain(|&j| j != column);
+    (merged, filled)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::f64::EPSILON;
-
     use super::*;
 
     #[test]
@@ -176,8 +316,8 @@ mod tests {
             .map(|x| c64::new(x as f64, 0.))
             .collect::<Vec<_>>();
 
-        let column_indices = vec![1, 0, 2, 2];
-        let row_pointers = vec![0, 1, 3, 4];
+        let column_indices = [1, 0, 2, 2];
+        let row_pointers = [0, 1, 3, 4];
 
         let vector = [2, 4, 3]
             .into_iter()
@@ -191,13 +331,13 @@ mod tests {
             &vector[..],
         );
 
-        assert!((result[0].re - 20.).abs() < EPSILON);
-        assert!((result[1].re - 8.).abs() < EPSILON);
-        assert!((result[2].re - 9.).abs() < EPSILON);
+        assert!((result[0].re - 20.).abs() < f64::EPSILON);
+        assert!((result[1].re - 8.).abs() < f64::EPSILON);
+        assert!((result[2].re - 9.).abs() < f64::EPSILON);
 
-        assert!((result[0].im).abs() < EPSILON);
-        assert!((result[1].im).abs() < EPSILON);
-        assert!((result[2].im).abs() < EPSILON);
+        assert!((result[0].im).abs() < f64::EPSILON);
+        assert!((result[1].im).abs() < f64::EPSILON);
+        assert!((result[2].im).abs() < f64::EPSILON);
     }
 
     #[test]
@@ -232,4 +372,78 @@ mod tests {
                 < 1e-8
         );
     }
+
+    #[test]
+    fn test_solve_direct_zero_diagonal() {
+        // [[0, 1], [1, 0]] x = [2, 3], as produced by a voltage source branch
+        let values = vec![c64::ONE, c64::ONE];
+        let column_indices = vec![1, 0];
+        let row_pointers = vec![0, 1, 2];
+        let b = vec![c64::real(2.), c64::real(3.)];
+
+        let x = solve_direct(&values, &column_indices, &row_pointers, &b).unwrap();
+
+        assert!((x[0].re - 3.).abs() < 1e-12);
+        assert!((x[1].re - 2.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_solve_direct_singular() {
+        let values = vec![c64::ONE, c64::ONE, c64::ONE, c64::ONE];
+        let column_indices = vec![0, 1, 0, 1];
+        let row_pointers = vec![0, 2, 4];
+        let b = vec![c64::ONE, c64::ONE];
+
+        assert_eq!(
+            solve_direct(&values, &column_indices, &row_pointers, &b),
+            Err(SingularMatrix { column: 1 })
+        );
+    }
+
+    #[test]
+    fn test_solve_direct_not_finite() {
+        let values = vec![c64::real(f64::NAN), c64::ONE, c64::ONE];
+        let column_indices = vec![0, 1, 0];
+        let row_pointers = vec![0, 2, 3];
+        let b = vec![c64::ONE, c64::ONE];
+
+        assert_eq!(
+            solve_direct(&values, &column_indices, &row_pointers, &b),
+            Err(SingularMatrix { column: 0 })
+        );
+    }
+
+    #[test]
+    fn test_solve_direct_sparse() {
+        // a chain of 1 ohm resistors from a 1V source down to ground, with the source's
+        // branch current as the last unknown
+        let n = 200;
+        let mut coordinates = vec![(0, n), (n, 0), (n - 1, n - 1)];
+        for i in 0..n - 1 {
+            coordinates.extend([(i, i), (i, i + 1), (i + 1, i), (i + 1, i + 1)]);
+        }
+
+        let mut le = crate::numerical::LinearEquations::from_coordinates(coordinates);
+        for i in 0..n - 1 {
+            le.add_a(i, i, c64::ONE);
+            le.add_a(i, i + 1, -c64::ONE);
+            le.add_a(i + 1, i, -c64::ONE);
+            le.add_a(i + 1, i + 1, c64::ONE);
+        }
+        le.add_a(n - 1, n - 1, c64::ONE);
+        le.stamp_voltage_source(0, None, n, c64::ONE);
+
+        le.solve_direct().unwrap();
+
+        // the voltage falls by the same amount over every resistor
+        for i in 0..n as usize {
+            let expected = 1. - i as f64 / n as f64;
+            assert!(
+                (le.x[i].re - expected).abs() < 1e-9,
+                "{} at {i}",
+                le.x[i].re
+            );
+        }
+        assert!((le.x[n as usize].re + 1. / n as f64).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,30 @@
+/// How close a solved quantity has to be, SPICE-style: `reltol` of its magnitude
+/// plus an absolute floor of `vntol` for voltages or `abstol` for currents.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    pub reltol: f64,
+    pub vntol: f64,
+    pub abstol: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            reltol: 1e-3,
+            vntol: 1e-6,
+            abstol: 1e-12,
+        }
+    }
+}
+
+impl Tolerance {
+    /// Allowed error in a voltage of magnitude `v`.
+    pub fn voltage(&self, v: f64) -> f64 {
+        self.reltol * v.abs() + self.vntol
+    }
+
+    /// Allowed error in a current of magnitude `i`.
+    pub fn current(&self, i: f64) -> f64 {
+        self.reltol * i.abs() + self.abstol
+    }
+}
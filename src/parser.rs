@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
-    circuit::Circuit,
-    expression::{Expression},
+    circuit::{Circuit, Unresolved},
+    component::{ComponentError, ComponentLibrary},
+    expression::{Expression, parse_expr},
+    si::parse_si_number,
 };
 
 #[derive(Debug, Clone)]
@@ -16,6 +18,15 @@ pub enum Command {
     },
 }
 
+/// Where and why a netlist can't be read, by 1-based line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The value is neither a number, an expression nor a quoted name.
+    InvalidParameterValue { line: usize, parameter: String },
+    /// The line is neither a comment nor a component.
+    UnexpectedInput { line: usize },
+}
+
 pub struct Parser {
     pos: usize,
     advancements: Vec<usize>,
@@ -58,6 +69,19 @@ impl Parser {
         }
     }
 
+    pub fn skip_inline_whitespace(&mut self) {
+        while !self.is_eof() && self.chars[self.pos] != '\n' && self.chars[self.pos].is_whitespace()
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// The 1-based line the parser is on.
+    pub fn line(&self) -> usize {
+        let pos = self.pos.min(self.chars.len());
+        self.chars[..pos].iter().filter(|&&c| c == '\n').count() + 1
+    }
+
     pub fn is_eof(&self) -> bool {
         self.pos >= self.chars.len()
     }
@@ -136,6 +160,35 @@ impl Parser {
         Some(value * 1_000)
     }
 
+    /// Parses a whitespace-delimited parameter value, either an SI-prefixed number (`4.7k`, `10u`),
+    /// an arbitrary expression (`1<90`, `sqrt(2)`) or the quoted name of a component (`"V1"`),
+    /// which is taken as a variable without subscript.
+    pub fn parse_parameter_value(&mut self) -> Option<Expression> {
+        if let Some(name) = self.parse_string() {
+            return Some(Expression::Variable {
+                name,
+                subscript: None,
+            });
+        }
+
+        let mut chars = vec![];
+
+        while let Some(c) = self.expect(|c| !c.is_whitespace()) {
+            chars.push(c);
+        }
+
+        let token: String = chars.into_iter().collect();
+
+        if let Some(value) = parse_si_number(&token) {
+            return Some(Expression::Real(value));
+        }
+
+        match parse_expr(&token) {
+            Ok((expression, "")) => Some(expression),
+            _ => None,
+        }
+    }
+
     pub fn parse_comment(&mut self) -> Option<String> {
         self.advance_push();
 
@@ -154,10 +207,9 @@ impl Parser {
         Some(chars.into_iter().collect::<String>().trim().to_string())
     }
 
-
-    pub fn parse_commands(&mut self) -> Option<Vec<Command>>{
+    pub fn parse_commands(&mut self) -> Result<Vec<Command>, ParseError> {
         let mut commands = vec![];
-        
+
         self.advance_push();
 
         loop {
@@ -171,7 +223,7 @@ impl Parser {
                 continue;
             }
 
-            if let Some(command) = self.parse_component_command() {
+            if let Some(command) = self.parse_component_command()? {
                 commands.push(command);
                 self.advance_drop();
                 continue;
@@ -181,22 +233,27 @@ impl Parser {
         }
 
         self.advance_drop();
-        Some(commands)
+
+        if !self.is_eof() {
+            return Err(ParseError::UnexpectedInput { line: self.line() });
+        }
+
+        Ok(commands)
     }
 
-    pub fn parse_component_command(&mut self) -> Option<Command> {
+    pub fn parse_component_command(&mut self) -> Result<Option<Command>, ParseError> {
         self.advance_push();
 
         let Some(kind) = self.parse_identifier() else {
             self.advance_pop();
-            return None;
+            return Ok(None);
         };
 
-        self.skip_whitespace();
+        self.skip_inline_whitespace();
 
         let name = self.parse_string();
         if name.is_some() {
-            self.skip_whitespace();
+            self.skip_inline_whitespace();
         }
 
         let mut terminals = Vec::new();
@@ -204,7 +261,7 @@ impl Parser {
         let mut parsing_params = false;
 
         loop {
-            self.skip_whitespace();
+            self.skip_inline_whitespace();
 
             let Some(key) = self.parse_identifier() else {
                 break;
@@ -213,9 +270,15 @@ impl Parser {
             if self.expect_char('=') {
                 parsing_params = true;
 
-                let value = self.parse_number_k().expect("number followed by K");
+                let line = self.line();
+                let Some(value) = self.parse_parameter_value() else {
+                    return Err(ParseError::InvalidParameterValue {
+                        line,
+                        parameter: key,
+                    });
+                };
 
-                params.insert(key, Expression::Real(value as f64));
+                params.insert(key, value);
             } else {
                 if parsing_params {
                     self.advance_pop();
@@ -228,19 +291,64 @@ impl Parser {
 
         self.advance_drop();
 
-        Some(Command::Component {
+        Ok(Some(Command::Component {
             component: kind,
             name,
             terminals,
             parameters: params.into_iter().collect(),
-        })
+        }))
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum BuildError {
+    UnknownComponent {
+        component: String,
+    },
+    TerminalCountMismatch {
+        component: String,
+        name: Option<String>,
+        expected: usize,
+        supplied: usize,
+    },
+    Component {
+        component: String,
+        name: Option<String>,
+        errors: Vec<ComponentError>,
+    },
+    /// A behavioral source references a net or component the circuit doesn't have.
+    UnresolvedVariable {
+        name: Option<String>,
+        variable: String,
+    },
+    /// A behavioral source references a parameter of another behavioral source.
+    BehavioralReference {
+        name: Option<String>,
+        variable: String,
+    },
+    /// A behavioral source calls a function that doesn't exist or doesn't take
+    /// that many arguments.
+    UnknownFunction {
+        name: Option<String>,
+        function: String,
+        arguments: usize,
+    },
+    /// Another component or behavioral source already has the name.
+    DuplicateName {
+        name: String,
+    },
+}
+
 pub struct CircuitBuilder {
     commands: Vec<Command>,
 }
 
+impl Default for CircuitBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CircuitBuilder {
     pub fn new() -> Self {
         Self {
@@ -252,7 +360,359 @@ impl CircuitBuilder {
         self.commands.extend(cmds);
     }
 
-    pub fn build(&self) -> Circuit {
-        todo!()
+    pub fn build(&self, library: &ComponentLibrary) -> Result<Circuit, Vec<BuildError>> {
+        let mut circuit = Circuit::new();
+        let mut errors = vec![];
+        let mut deferred = vec![];
+        let mut names = HashSet::new();
+
+        let construct = |circuit: &mut Circuit,
+                         component: &String,
+                         name: &Option<String>,
+                         terminals: Vec<u32>,
+                         parameters: &HashMap<String, Expression>| {
+            let result = library
+                .construct(
+                    component,
+                    circuit,
+                    name.clone(),
+                    &terminals,
+                    parameters.clone(),
+                )
+                .expect("terminal count implies the component is registered");
+
+            result.err().map(|errors| BuildError::Component {
+                component: component.clone(),
+                name: name.clone(),
+                errors,
+            })
+        };
+
+        for command in &self.commands {
+            let Command::Component {
+                component,
+                name,
+                terminals,
+                parameters,
+            } = command
+            else {
+                continue;
+            };
+
+            let Some(expected) = library.terminal_count_of(component) else {
+                errors.push(BuildError::UnknownComponent {
+                    component: component.clone(),
+                });
+                continue;
+            };
+
+            if let Some(name) = name
+                && !names.insert(name)
+            {
+                errors.push(BuildError::DuplicateName { name: name.clone() });
+                continue;
+            }
+
+            if expected != terminals.len() {
+                errors.push(BuildError::TerminalCountMismatch {
+                    component: component.clone(),
+                    name: name.clone(),
+                    expected,
+                    supplied: terminals.len(),
+                });
+                continue;
+            }
+
+            let terminals: Vec<u32> = terminals.iter().map(|net| circuit.net(net)).collect();
+
+            // those referring to other components wait until all of them are in
+            if library.is_deferred(component) {
+                deferred.push((component, name, terminals, parameters));
+            } else {
+                errors.extend(construct(
+                    &mut circuit,
+                    component,
+                    name,
+                    terminals,
+                    parameters,
+                ));
+            }
+        }
+
+        for (component, name, terminals, parameters) in deferred {
+            errors.extend(construct(
+                &mut circuit,
+                component,
+                name,
+                terminals,
+                parameters,
+            ));
+        }
+
+        errors.extend(circuit.resolve_behavioral().into_iter().map(
+            |unresolved| match unresolved {
+                Unresolved::Variable { name, variable } => {
+                    BuildError::UnresolvedVariable { name, variable }
+                }
+                Unresolved::Behavioral { name, variable } => {
+                    BuildError::BehavioralReference { name, variable }
+                }
+                Unresolved::Function {
+                    name,
+                    function,
+                    arguments,
+                } => BuildError::UnknownFunction {
+                    name,
+                    function,
+                    arguments,
+                },
+            },
+        ));
+
+        if errors.is_empty() {
+            Ok(circuit)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Builds `netlist` from the builtin components, for the tests of the components.
+#[cfg(test)]
+pub(crate) fn build(netlist: &str) -> Circuit {
+    let mut builder = CircuitBuilder::new();
+    builder.add_commands(Parser::from(netlist).parse_commands().unwrap());
+    builder.build(&ComponentLibrary::with_builtins()).unwrap()
+}
+
+/// The parameters reported invalid when building `netlist`, which has to fail for that.
+#[cfg(test)]
+pub(crate) fn invalid_parameters(netlist: &str) -> Vec<String> {
+    let mut builder = CircuitBuilder::new();
+    builder.add_commands(Parser::from(netlist).parse_commands().unwrap());
+
+    let errors = builder
+        .build(&ComponentLibrary::with_builtins())
+        .err()
+        .unwrap();
+    let [BuildError::Component { errors, .. }] = &errors[..] else {
+        panic!("unexpected errors {:?}", errors);
+    };
+
+    errors
+        .iter()
+        .filter_map(|error| match error {
+            ComponentError::InvalidParameter { parameter } => Some(parameter.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> ComponentLibrary {
+        ComponentLibrary::with_builtins()
+    }
+
+    fn builder(netlist: &str) -> CircuitBuilder {
+        let mut builder = CircuitBuilder::new();
+        builder.add_commands(Parser::from(netlist).parse_commands().unwrap());
+        builder
+    }
+
+    #[test]
+    fn test_build_resolves_nets() {
+        let netlist = "
+            -- divider
+            resistor \"R1\" in mid R=1k
+            resistor \"R2\" mid out R=1k
+            ground out
+        ";
+
+        let circuit = builder(netlist).build(&library()).unwrap();
+
+        assert_eq!(circuit.net_index("in"), Some(0));
+        assert_eq!(circuit.net_index("mid"), Some(1));
+        assert_eq!(circuit.net_index("out"), Some(2));
+        assert_eq!(circuit.net_index("nowhere"), None);
+        assert_eq!(circuit.nets().count(), 3);
+    }
+
+    #[test]
+    fn test_build_reports_errors() {
+        let netlist = "
+            flux-capacitor \"F1\" in out
+            resistor \"R1\" in R=1k
+        ";
+
+        let errors = builder(netlist).build(&library()).err().unwrap();
+
+        assert!(matches!(
+            &errors[..],
+            [
+                BuildError::UnknownComponent { .. },
+                BuildError::TerminalCountMismatch {
+                    expected: 2,
+                    supplied: 1,
+                    ..
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_build_reports_parameter_errors() {
+        let netlist = "
+            resistor \"R1\" in out X=1k
+            capacitor \"C1\" in out C=10u L=2m
+            resistor \"R2\" in out R=1<90
+        ";
+
+        let errors = builder(netlist).build(&library()).err().unwrap();
+
+        let [
+            BuildError::Component { errors: r1, .. },
+            BuildError::Component { errors: c1, .. },
+            BuildError::Component { errors: r2, .. },
+        ] = &errors[..]
+        else {
+            panic!("unexpected errors {:?}", errors);
+        };
+
+        assert!(matches!(
+            &r1[..],
+            [ComponentError::MissingRequiredParameter { parameter }] if parameter == "R"
+        ));
+        assert!(matches!(
+            &c1[..],
+            [ComponentError::UnusedSuppliedParameter { parameter }] if parameter == "L"
+        ));
+        // rather than dropping the imaginary part
+        assert!(matches!(
+            &r2[..],
+            [ComponentError::InvalidParameter { parameter }] if parameter == "R"
+        ));
+    }
+
+    #[test]
+    fn test_build_reports_non_constant_parameters() {
+        let netlist = "
+            resistor \"R1\" in out R=1k*V_x
+            ccvs \"H1\" h gnd R=2k control=1
+        ";
+
+        let errors = builder(netlist).build(&library()).err().unwrap();
+
+        let [
+            BuildError::Component { errors: r1, .. },
+            BuildError::Component { errors: h1, .. },
+        ] = &errors[..]
+        else {
+            panic!("unexpected errors {:?}", errors);
+        };
+
+        assert!(matches!(
+            &r1[..],
+            [ComponentError::InvalidParameter { parameter }] if parameter == "R"
+        ));
+        assert!(matches!(
+            &h1[..],
+            [ComponentError::InvalidParameter { parameter }] if parameter == "control"
+        ));
+    }
+
+    #[test]
+    fn test_build_resolves_current_controls() {
+        // H1 names V1 before it is in the netlist
+        let netlist = "
+            ccvs \"H1\" h gnd R=2k control=\"V1\"
+            dc-source-1-terminal \"V1\" in V=1
+            resistor \"R1\" in gnd R=1k
+            resistor \"R2\" h gnd R=1k
+            ground gnd
+        ";
+
+        let mut circuit = builder(netlist).build(&library()).unwrap();
+        circuit.step(1.).unwrap();
+
+        let ic = circuit.parameter("H1", "Ic").unwrap();
+        assert!((ic.re + 1e-3).abs() < 1e-12);
+        assert!((circuit.voltage("h").unwrap().re + 2.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_build_reports_unknown_controls() {
+        let netlist = "
+            cccs \"F1\" f gnd K=5 control=\"R1\"
+            resistor \"R1\" f gnd R=1k
+            ground gnd
+        ";
+
+        let errors = builder(netlist).build(&library()).err().unwrap();
+
+        assert!(matches!(
+            &errors[..],
+            [BuildError::Component { errors, .. }]
+                if matches!(&errors[..], [ComponentError::UnknownControl { component }] if component == "R1")
+        ));
+    }
+
+    #[test]
+    fn test_construct_checks_terminal_count() {
+        let mut circuit = Circuit::new();
+        let parameters = [("R".to_string(), Expression::Real(1e3))].into();
+
+        let errors = library()
+            .construct("resistor", &mut circuit, None, &[0, 1, 2], parameters)
+            .unwrap()
+            .err()
+            .unwrap();
+
+        assert!(matches!(
+            &errors[..],
+            [ComponentError::TerminalCount {
+                expected: 2,
+                supplied: 3
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_parse_parameter_values() {
+        let commands = Parser::from("ac-source-1-terminal in V=2.5 f=1k phi=1.5m")
+            .parse_commands()
+            .unwrap();
+
+        let [Command::Component { parameters, .. }] = &commands[..] else {
+            panic!("expected a single component");
+        };
+
+        let value = |p: &str| parameters[p].compute_fixed().unwrap().re;
+
+        assert!((value("V") - 2.5).abs() < 1e-12);
+        assert!((value("f") - 1e3).abs() < 1e-12);
+        assert!((value("phi") - 1.5e-3).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let malformed = "resistor a b R=1k\nresistor b c R=1k+\nresistor c d R=1k";
+
+        assert_eq!(
+            Parser::from(malformed).parse_commands().unwrap_err(),
+            ParseError::InvalidParameterValue {
+                line: 2,
+                parameter: "R".into()
+            }
+        );
+
+        let unexpected = "resistor a b R=1k\n-- divider\n\n42 b c R=1k";
+
+        assert_eq!(
+            Parser::from(unexpected).parse_commands().unwrap_err(),
+            ParseError::UnexpectedInput { line: 4 }
+        );
     }
 }
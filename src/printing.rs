@@ -30,11 +30,11 @@ pub fn print_table(
         }
 
         let r = table.add_row(Row::empty());
-        r.add_cell(Cell::new(name.as_ref().map(|s| s.as_str()).unwrap_or("")));
+        r.add_cell(Cell::new(name.as_deref().unwrap_or("")));
 
         for h in &headers {
             let value = match row.get(h) {
-                Some(z) => format_complex_si_unitful(*z, var_to_si_unit(&h).unwrap_or("")),
+                Some(z) => format_complex_si_unitful(*z, var_to_si_unit(h).unwrap_or("")),
                 None => "".to_string(),
             };
 
@@ -114,5 +114,5 @@ pub fn print_chart(chart_name: impl ToString, points: Vec<(f64, f64)>) -> String
 
     c.axis();
     c.figures();
-    format!("{}\n{}", chart_name.to_string(), c.to_string())
+    format!("{}\n{}", chart_name.to_string(), c)
 }
@@ -46,7 +46,7 @@ pub fn format_complex_si_unitful(z: c64, unit: &str) -> String {
 
     for (mult, pre) in SI_PREFIXES.iter() {
         let test = mag / mult;
-        if test >= 1.0 && test < 1000.0 {
+        if (1.0..1000.0).contains(&test) {
             scaled = test;
             prefix = pre;
             break;
@@ -60,7 +60,7 @@ pub fn format_complex_si_unitful(z: c64, unit: &str) -> String {
         (5 - digits).max(0) as usize
     };
 
-    let formatted_mag = if scaled >= 1000.0 || scaled < 1e-12 {
+    let formatted_mag = if !(1e-12..1000.0).contains(&scaled) {
         format!("{:.3E}", mag)
     } else {
         format!("{:.*}", decimal_places, scaled)